@@ -0,0 +1,139 @@
+use crate::{Change, CrateVersion, RegistryConfig};
+use std::collections::HashMap;
+
+/// What's known about a single crate version across all changes to it.
+struct Entry {
+    /// The position of the first change to the version, where the net effect will be placed.
+    slot: usize,
+    /// The version before the first change, or `None` if it didn't exist.
+    before: Option<CrateVersion>,
+    /// The version after the last change, or `None` if it doesn't exist anymore.
+    after: Option<CrateVersion>,
+}
+
+/// Compaction
+impl Change {
+    /// Collapse `changes`, which must be in the order they were made, into their net effect per crate version,
+    /// identified by the name of the crate and its checksum.
+    ///
+    /// For example, [`Added`](Change::Added) followed by [`Yanked`](Change::Yanked) becomes
+    /// [`AddedAndYanked`](Change::AddedAndYanked), and [`Added`](Change::Added) followed by
+    /// [`VersionDeleted`](Change::VersionDeleted) disappears entirely.
+    /// Changes to the registry configuration are collapsed as well.
+    ///
+    /// The net effect of all changes to a version takes the place of the first of these changes, and
+    /// [`CrateDeleted`](Change::CrateDeleted) absorbs all changes to versions that still existed when the crate
+    /// was deleted.
+    /// This is useful for consumers that only need to sync to the final state.
+    pub fn compact(changes: impl IntoIterator<Item = Change>) -> Vec<Change> {
+        let mut slots: Vec<Option<Change>> = Vec::new();
+        let mut versions = HashMap::<(String, [u8; 32]), Entry>::new();
+        let mut config: Option<(usize, Option<RegistryConfig>, Option<RegistryConfig>)> = None;
+
+        for change in changes {
+            let (before, after) = match change {
+                Change::Added(v) => (None, Some(v)),
+                Change::AddedAndYanked(v) => (None, Some(v)),
+                Change::Yanked(v) => (Some(with_yanked(&v, false)), Some(v)),
+                Change::Unyanked(v) => (Some(with_yanked(&v, true)), Some(v)),
+                Change::VersionDeleted(v) => (Some(v), None),
+                Change::Modified { old, new } => (Some(*old), Some(*new)),
+                Change::ConfigChanged { old, new } => {
+                    match &mut config {
+                        Some((_, _, after)) => *after = new,
+                        None => {
+                            config = Some((slots.len(), old, new));
+                            slots.push(None);
+                        }
+                    }
+                    continue;
+                }
+                Change::CrateDeleted {
+                    name,
+                    versions: mut deleted,
+                } => {
+                    let crate_name = name.to_ascii_lowercase();
+                    let num_deleted = deleted.len();
+                    let keys: Vec<_> = versions
+                        .keys()
+                        .filter(|(name, _)| *name == crate_name)
+                        .cloned()
+                        .collect();
+                    for key in keys {
+                        let entry = versions.remove(&key).expect("key was just found");
+                        if entry.after.is_some() {
+                            if entry.before.is_none() {
+                                deleted.retain(|v| v.checksum != key.1);
+                            }
+                        } else {
+                            slots[entry.slot] = net_effect(entry.before, entry.after);
+                        }
+                    }
+                    if num_deleted == 0 || !deleted.is_empty() {
+                        slots.push(Some(Change::CrateDeleted {
+                            name,
+                            versions: deleted,
+                        }));
+                    }
+                    continue;
+                }
+            };
+            let version = after
+                .as_ref()
+                .or(before.as_ref())
+                .expect("at least one side exists");
+            let key = (version.name.to_ascii_lowercase(), version.checksum);
+            match versions.get_mut(&key) {
+                Some(entry) => entry.after = after,
+                None => {
+                    versions.insert(
+                        key,
+                        Entry {
+                            slot: slots.len(),
+                            before,
+                            after,
+                        },
+                    );
+                    slots.push(None);
+                }
+            }
+        }
+
+        for entry in versions.into_values() {
+            slots[entry.slot] = net_effect(entry.before, entry.after);
+        }
+        if let Some((slot, old, new)) = config {
+            if old != new {
+                slots[slot] = Some(Change::ConfigChanged { old, new });
+            }
+        }
+        slots.into_iter().flatten().collect()
+    }
+}
+
+/// Return the change that turns a version from `before` into `after`, or `None` if there is no difference.
+fn net_effect(before: Option<CrateVersion>, after: Option<CrateVersion>) -> Option<Change> {
+    Some(match (before, after) {
+        (None, None) => return None,
+        (None, Some(new)) if new.yanked => Change::AddedAndYanked(new),
+        (None, Some(new)) => Change::Added(new),
+        (Some(old), None) => Change::VersionDeleted(old),
+        (Some(old), Some(new)) => match (old.yanked, new.yanked) {
+            (true, false) => Change::Unyanked(new),
+            (false, true) => Change::Yanked(new),
+            _ if old == new => return None,
+            _ => Change::Modified {
+                old: old.into(),
+                new: new.into(),
+            },
+        },
+    })
+}
+
+/// Return a copy of `version` with its yanked state set to `yanked`.
+fn with_yanked(version: &CrateVersion, yanked: bool) -> CrateVersion {
+    CrateVersion {
+        yanked,
+        ..version.clone()
+    }
+}
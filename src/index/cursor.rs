@@ -0,0 +1,207 @@
+use crate::index::diff::{Error, Order, Outcome};
+use crate::index::CURSOR_REFNAME_PREFIX;
+use crate::{Change, Index};
+use std::convert::TryFrom;
+use std::sync::atomic::AtomicBool;
+
+/// A named position in the history of the crates index, allowing multiple consumers to share one clone
+/// while learning about changes independently.
+///
+/// Each cursor is stored as reference in the `refs/crates-index-diff/cursors/` namespace of the
+/// underlying repository.
+/// Obtain one with [`Index::cursor()`].
+pub struct Cursor<'index> {
+    index: &'index Index,
+    name: String,
+    ref_name: gix::refs::FullName,
+}
+
+/// Cursors
+impl Index {
+    /// Return the cursor called `name`, like `docs-builder`, which is created once it is
+    /// [acknowledged](Cursor::ack()) for the first time.
+    ///
+    /// `name` must be usable as part of a reference name.
+    pub fn cursor(&self, name: impl Into<String>) -> Result<Cursor<'_>, Error> {
+        let name = name.into();
+        let ref_name = gix::refs::FullName::try_from(format!("{CURSOR_REFNAME_PREFIX}{name}"))
+            .map_err(|source| Error::InvalidCursorName {
+                name: name.clone(),
+                source,
+            })?;
+        Ok(Cursor {
+            index: self,
+            name,
+            ref_name,
+        })
+    }
+
+    /// Return the names of all cursors that were acknowledged at least once, sorted by name.
+    pub fn cursors(&self) -> Result<Vec<String>, Error> {
+        let mut names = Vec::new();
+        for reference in self
+            .repo
+            .references()
+            .map_err(|err| Error::ListCursors(err.into()))?
+            .prefixed(CURSOR_REFNAME_PREFIX)
+            .map_err(|err| Error::ListCursors(err.into()))?
+        {
+            let reference = reference.map_err(Error::ListCursors)?;
+            if let Some(name) = reference
+                .name()
+                .as_bstr()
+                .strip_prefix(CURSOR_REFNAME_PREFIX.as_bytes())
+            {
+                names.push(String::from_utf8_lossy(name).into_owned());
+            }
+        }
+        names.sort();
+        Ok(names)
+    }
+}
+
+impl Cursor<'_> {
+    /// Return the name of this cursor.
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// Return the full name of the reference that stores the position of this cursor.
+    pub fn reference_name(&self) -> &gix::refs::FullNameRef {
+        self.ref_name.as_ref()
+    }
+
+    /// Return the commit this cursor was last acknowledged at, or `None` if it was never acknowledged.
+    pub fn position(&self) -> Option<gix::hash::ObjectId> {
+        self.index
+            .repo
+            .find_reference(self.ref_name.as_ref())
+            .ok()
+            .and_then(|r| r.try_id().map(|id| id.detach()))
+    }
+
+    /// Return all changes between the [position](Self::position()) of this cursor and `to`, in the given `order`,
+    /// without moving the cursor.
+    ///
+    /// If the cursor was never acknowledged, all changes since the beginning of history are returned,
+    /// unless [`Index::start_from()`] says otherwise.
+    /// Use [`Index::fetch_remote()`] to obtain `to` once for all cursors.
+    pub fn peek(&self, to: gix::hash::ObjectId, order: Order) -> Result<Vec<Change>, Error> {
+        Ok(self.peek_with_outcome(to, order)?.changes)
+    }
+
+    /// Like [`Self::peek()`], but provides everything else that was learned while finding the changes as well,
+    /// like the lines that were [skipped](crate::index::diff::DecodePolicy::SkipAndReport).
+    pub fn peek_with_outcome(
+        &self,
+        to: gix::hash::ObjectId,
+        order: Order,
+    ) -> Result<Outcome, Error> {
+        self.index.changes_between_commits_with_outcome(
+            self.index
+                .start_of_changes(self.position_or_empty_tree(), to),
+            to,
+            order,
+            gix::progress::Discard,
+            &AtomicBool::default(),
+        )
+    }
+
+    /// Fetch the remote, return all changes since the [position](Self::position()) of this cursor
+    /// and [acknowledge](Self::ack()) them right away.
+    ///
+    /// This is like [`Index::fetch_changes_with_options()`], but for this cursor.
+    pub fn fetch<P>(
+        &self,
+        progress: P,
+        should_interrupt: &AtomicBool,
+        order: Order,
+    ) -> Result<Vec<Change>, Error>
+    where
+        P: gix::NestedProgress,
+        P::SubProgress: 'static,
+    {
+        Ok(self
+            .fetch_with_outcome(progress, should_interrupt, order)?
+            .changes)
+    }
+
+    /// Like [`Self::fetch()`], but provides everything else that was learned while finding the changes as well,
+    /// like the lines that were [skipped](crate::index::diff::DecodePolicy::SkipAndReport).
+    pub fn fetch_with_outcome<P>(
+        &self,
+        mut progress: P,
+        should_interrupt: &AtomicBool,
+        order: Order,
+    ) -> Result<Outcome, Error>
+    where
+        P: gix::NestedProgress,
+        P::SubProgress: 'static,
+    {
+        let from = self.position_or_empty_tree();
+        let to = self
+            .index
+            .fetch_remote_inner(&mut progress, should_interrupt, Some(from))?;
+        let outcome = self.index.changes_between_commits_with_outcome(
+            self.index.start_of_changes(from, to),
+            to,
+            order,
+            progress.add_child("diff"),
+            should_interrupt,
+        )?;
+        self.ack(to)?;
+        Ok(outcome)
+    }
+
+    /// Move this cursor to `to`, marking all changes up to it as seen.
+    ///
+    /// Use [`Self::acknowledge()`] instead if multiple processes may move this cursor.
+    pub fn ack(&self, to: gix::hash::ObjectId) -> Result<(), Error> {
+        self.index.repo.reference(
+            self.ref_name.as_ref(),
+            to,
+            gix::refs::transaction::PreviousValue::Any,
+            format!("moving cursor '{}' to latest seen commit", self.name),
+        )?;
+        Ok(())
+    }
+
+    /// Move this cursor to `to` like [`Index::acknowledge()`] does for the last seen reference, but only if its
+    /// [position](Self::position()) is still `expected_previous` and `to` contains it in its history.
+    pub fn acknowledge(
+        &self,
+        expected_previous: Option<gix::hash::ObjectId>,
+        to: gix::hash::ObjectId,
+    ) -> Result<(), Error> {
+        self.acknowledge_inner(expected_previous, to, false)
+    }
+
+    /// Like [`Self::acknowledge()`], but allows `to` to not contain `expected_previous` in its history.
+    pub fn acknowledge_forced(
+        &self,
+        expected_previous: Option<gix::hash::ObjectId>,
+        to: gix::hash::ObjectId,
+    ) -> Result<(), Error> {
+        self.acknowledge_inner(expected_previous, to, true)
+    }
+
+    fn acknowledge_inner(
+        &self,
+        expected_previous: Option<gix::hash::ObjectId>,
+        to: gix::hash::ObjectId,
+        force: bool,
+    ) -> Result<(), Error> {
+        self.index.acknowledge_inner(
+            self.ref_name.clone(),
+            expected_previous,
+            to,
+            force,
+            format!("moving cursor '{}' to latest seen commit", self.name),
+        )
+    }
+
+    fn position_or_empty_tree(&self) -> gix::hash::ObjectId {
+        self.position()
+            .unwrap_or_else(|| gix::hash::ObjectId::empty_tree(self.index.repo.object_hash()))
+    }
+}
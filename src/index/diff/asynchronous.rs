@@ -0,0 +1,125 @@
+use crate::index::diff::{github, Error, Order, Outcome};
+use crate::{Change, Index};
+use std::future::Future;
+use std::sync::atomic::AtomicBool;
+use std::sync::Arc;
+
+/// Find changes from within an async runtime
+impl Index {
+    /// Like [`Self::peek_changes_with_options()`], but without blocking the calling thread.
+    ///
+    /// The GitHub fast-path is checked asynchronously, so nothing else is done if there are no changes.
+    /// Otherwise, fetching and diffing happen on the blocking thread-pool of the `tokio` runtime this is awaited in,
+    /// as `gix` can't use an async transport while its blocking one is in use.
+    /// The diff can be interrupted by setting `should_interrupt`.
+    ///
+    /// The returned future doesn't borrow this instance, and can thus be spawned onto the runtime.
+    ///
+    /// # Panics
+    ///
+    /// If not awaited within a `tokio` runtime.
+    pub fn peek_changes_async(
+        &self,
+        should_interrupt: Arc<AtomicBool>,
+        order: Order,
+    ) -> impl Future<Output = Result<(Vec<Change>, gix::hash::ObjectId), Error>> + Send + 'static
+    {
+        let outcome = self.changes_async(should_interrupt, order, false);
+        async move {
+            let (outcome, to) = outcome.await?;
+            Ok((outcome.changes, to))
+        }
+    }
+
+    /// Like [`Self::peek_changes_with_outcome()`], but without blocking the calling thread,
+    /// just like [`Self::peek_changes_async()`].
+    pub fn peek_changes_with_outcome_async(
+        &self,
+        should_interrupt: Arc<AtomicBool>,
+        order: Order,
+    ) -> impl Future<Output = Result<(Outcome, gix::hash::ObjectId), Error>> + Send + 'static {
+        self.changes_async(should_interrupt, order, false)
+    }
+
+    /// Like [`Self::fetch_changes_with_options()`], but without blocking the calling thread,
+    /// just like [`Self::peek_changes_async()`].
+    pub fn fetch_changes_async(
+        &self,
+        should_interrupt: Arc<AtomicBool>,
+        order: Order,
+    ) -> impl Future<Output = Result<Vec<Change>, Error>> + Send + 'static {
+        let outcome = self.changes_async(should_interrupt, order, true);
+        async move { Ok(outcome.await?.0.changes) }
+    }
+
+    /// Like [`Self::fetch_changes_with_outcome()`], but without blocking the calling thread,
+    /// just like [`Self::peek_changes_async()`].
+    pub fn fetch_changes_with_outcome_async(
+        &self,
+        should_interrupt: Arc<AtomicBool>,
+        order: Order,
+    ) -> impl Future<Output = Result<Outcome, Error>> + Send + 'static {
+        let outcome = self.changes_async(should_interrupt, order, true);
+        async move { Ok(outcome.await?.0) }
+    }
+
+    /// Return all changes since the last seen commit and the commit they lead up to, and move the last seen
+    /// reference there if `set_last_seen` is `true`.
+    fn changes_async(
+        &self,
+        should_interrupt: Arc<AtomicBool>,
+        order: Order,
+        set_last_seen: bool,
+    ) -> impl Future<Output = Result<(Outcome, gix::hash::ObjectId), Error>> + Send + 'static {
+        let from = self.last_seen_or_empty_tree();
+        let url = self.remote().and_then(|remote| {
+            Ok(remote
+                .sanitized_url_and_version(gix::remote::Direction::Fetch)?
+                .0)
+        });
+        let index = self.clone();
+        async move {
+            if matches!(
+                github::has_changes_async(
+                    &url?,
+                    &from,
+                    &index.branch_name,
+                    &index.github_fast_path
+                )
+                .await?,
+                github::FastPath::UpToDate
+            ) {
+                let outcome = Outcome {
+                    changes: Vec::new(),
+                    order,
+                    diagnostics: Vec::new(),
+                    squash: None,
+                };
+                return Ok((outcome, from));
+            }
+
+            let res = tokio::task::spawn_blocking(move || {
+                let to =
+                    index.fetch_remote_inner(gix::progress::Discard, &should_interrupt, None)?;
+                let outcome = index.changes_between_commits_with_outcome(
+                    index.start_of_changes(from, to),
+                    to,
+                    order,
+                    gix::progress::Discard,
+                    &should_interrupt,
+                )?;
+                if set_last_seen {
+                    index.set_last_seen_reference(to)?;
+                }
+                Ok((outcome, to))
+            })
+            .await;
+            match res {
+                Ok(res) => res,
+                Err(err) if err.is_panic() => std::panic::resume_unwind(err.into_panic()),
+                // The runtime is shutting down.
+                Err(_) => Err(Error::Interrupted),
+            }
+        }
+    }
+}
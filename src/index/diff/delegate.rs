@@ -1,25 +1,40 @@
-use crate::index::diff::Error;
-use crate::{Change, CrateVersion};
+use crate::index::diff::{CrateFilter, DecodeDiagnostic, DecodePolicy, Error};
+use crate::{Change, CrateVersion, Dependency, DependencyKind, RegistryConfig};
 use ahash::{AHashSet, RandomState};
-use bstr::BStr;
+use bstr::{BStr, ByteSlice};
 use hashbrown::HashTable;
+use std::collections::HashMap;
 use std::hash::Hasher;
 use std::ops::Deref;
 
+/// The name of the file at the root of the index which holds the [`RegistryConfig`].
+pub(crate) const CONFIG_FILE_NAME: &str = "config.json";
+
 #[derive(Default)]
 pub(crate) struct Delegate {
     changes: Vec<Change>,
     /// All changes that happen within a file, along the line-number it happens in .
     per_file_changes: Vec<(usize, Change)>,
     err: Option<Error>,
+    decode_policy: DecodePolicy,
+    crate_filter: CrateFilter,
+    /// All lines that were skipped as they couldn't be decoded.
+    diagnostics: Vec<DecodeDiagnostic>,
 }
 
 impl Delegate {
+    pub fn new(decode_policy: DecodePolicy, crate_filter: CrateFilter) -> Self {
+        Delegate {
+            decode_policy,
+            crate_filter,
+            ..Default::default()
+        }
+    }
+
     pub fn handle(
         &mut self,
         change: gix::object::tree::diff::Change<'_, '_, '_>,
     ) -> Result<gix::object::tree::diff::Action, Error> {
-        use gix::bstr::ByteSlice;
         use gix::object::tree::diff::Change::*;
         use gix::objs::tree::EntryKind::*;
         fn entry_data(
@@ -31,9 +46,14 @@ impl Delegate {
                 .transpose()
                 .map_err(Into::into)
         }
+        // This also skips the registry configuration, which is only looked at in the root of the index.
         if change.location().contains(&b'.') {
             return Ok(Default::default());
         }
+        // Skip crates we aren't interested in before their files are loaded.
+        if change.entry_mode().is_no_tree() && !self.crate_filter.matches(change.location()) {
+            return Ok(Default::default());
+        }
 
         match change {
             Rewrite { .. } => {
@@ -46,15 +66,7 @@ impl Delegate {
                 ..
             } => {
                 if let Some(obj) = entry_data(entry_mode.kind(), id)? {
-                    for line in obj.data.lines() {
-                        let version = version_from_json_line(line, location)?;
-                        let change = if version.yanked {
-                            Change::AddedAndYanked(version)
-                        } else {
-                            Change::Added(version)
-                        };
-                        self.changes.push(change)
-                    }
+                    self.added(location, &obj.data)?;
                 }
             }
             Deletion {
@@ -65,14 +77,7 @@ impl Delegate {
             } => {
                 if entry_mode.is_no_tree() {
                     let obj = id.object()?;
-                    let mut deleted = Vec::with_capacity(obj.data.lines().count());
-                    for line in obj.data.lines() {
-                        deleted.push(version_from_json_line(line, location)?);
-                    }
-                    self.changes.push(Change::CrateDeleted {
-                        name: location.to_string(),
-                        versions: deleted,
-                    });
+                    self.deleted(location, &obj.data)?;
                 }
             }
             Modification {
@@ -85,71 +90,196 @@ impl Delegate {
                 if entry_mode.is_blob() {
                     let old = previous_id.object()?.into_blob();
                     let new = id.object()?.into_blob();
-                    let mut old_lines = AHashSet::with_capacity(1024);
-                    for (number, line) in old.data.lines().enumerate() {
-                        old_lines.insert(Line(number, line));
-                    }
-
-                    // A HashTable is used to represent a Checksum -> CrateVersion map
-                    // because the checksum is already stored in the CrateVersion
-                    // and we want to avoid storing the checksum twice for performance reasons
-                    let mut new_versions = HashTable::with_capacity(old_lines.len().min(1024));
-                    let hasher = RandomState::new();
-
-                    for (number, line) in new.data.lines().enumerate() {
-                        // first quickly check if the exact same line is already present in this file in that case we don't need to do anything else
-                        if old_lines.remove(&Line(number, line)) {
-                            continue;
-                        }
-                        // no need to check if the checksum already exists in the hashmap
-                        // as each checksum appears only once
-                        let new_version = version_from_json_line(line, location)?;
-                        new_versions.insert_unique(
-                            hasher.hash_one(new_version.checksum),
-                            (number, new_version),
-                            |rehashed| hasher.hash_one(rehashed.1.checksum),
-                        );
-                    }
-
-                    for line in old_lines.drain() {
-                        let old_version = version_from_json_line(&line, location)?;
-                        let new_version: Option<(usize, CrateVersion)> = new_versions
-                            .find_entry(hasher.hash_one(old_version.checksum), |version| {
-                                version.1.checksum == old_version.checksum
-                            })
-                            .map(|entry| entry.remove().0)
-                            .ok();
-                        match new_version {
-                            Some((_, new_version)) => {
-                                let change = match (old_version.yanked, new_version.yanked) {
-                                    (true, false) => Change::Unyanked(new_version),
-                                    (false, true) => Change::Yanked(new_version),
-                                    _ => continue,
-                                };
-                                self.per_file_changes.push((line.0, change))
-                            }
-                            None => self
-                                .per_file_changes
-                                .push((line.0, Change::VersionDeleted(old_version))),
-                        }
-                    }
-                    for (number, version) in new_versions.drain() {
-                        let change = if version.yanked {
-                            Change::AddedAndYanked(version)
-                        } else {
-                            Change::Added(version)
-                        };
-                        self.per_file_changes.push((number, change));
-                    }
-                    self.per_file_changes.sort_by_key(|t| t.0);
-                    self.changes
-                        .extend(self.per_file_changes.drain(..).map(|t| t.1));
+                    self.modified(location, &old.data, &new.data)?;
                 }
             }
         }
         Ok(Default::default())
     }
 
+    /// Record all versions in the crate file at `location` with content `data` as added.
+    pub fn added(&mut self, location: &BStr, data: &[u8]) -> Result<(), Error> {
+        for (number, line) in data.lines().enumerate() {
+            let Some(version) = self.decode(line, number, location)? else {
+                continue;
+            };
+            let change = if version.yanked {
+                Change::AddedAndYanked(version)
+            } else {
+                Change::Added(version)
+            };
+            self.changes.push(change)
+        }
+        Ok(())
+    }
+
+    /// Record the crate file at `location` with content `data` as deleted, along with all of its versions.
+    pub fn deleted(&mut self, location: &BStr, data: &[u8]) -> Result<(), Error> {
+        let mut deleted = Vec::with_capacity(data.lines().count());
+        for (number, line) in data.lines().enumerate() {
+            deleted.extend(self.decode(line, number, location)?);
+        }
+        self.changes.push(Change::CrateDeleted {
+            name: location.to_string(),
+            versions: deleted,
+        });
+        Ok(())
+    }
+
+    /// Record the line-by-line changes needed to turn the crate file at `location` from `old` into `new`.
+    ///
+    /// If lines of `new` are skipped as they can't be decoded, no version is reported as deleted as it might
+    /// be on one of these lines.
+    pub fn modified(&mut self, location: &BStr, old: &[u8], new: &[u8]) -> Result<(), Error> {
+        let mut old_lines = AHashSet::with_capacity(1024);
+        for (number, line) in old.lines().enumerate() {
+            old_lines.insert(Line(number, line));
+        }
+
+        // A HashTable is used to represent a Checksum -> CrateVersion map
+        // because the checksum is already stored in the CrateVersion
+        // and we want to avoid storing the checksum twice for performance reasons
+        let mut new_versions = HashTable::with_capacity(old_lines.len().min(1024));
+        let hasher = RandomState::new();
+
+        let num_diagnostics = self.diagnostics.len();
+        for (number, line) in new.lines().enumerate() {
+            // first quickly check if the exact same line is already present in this file in that case we don't need to do anything else
+            if old_lines.remove(&Line(number, line)) {
+                continue;
+            }
+            // no need to check if the checksum already exists in the hashmap
+            // as each checksum appears only once
+            let Some(new_version) = self.decode(line, number, location)? else {
+                continue;
+            };
+            new_versions.insert_unique(
+                hasher.hash_one(new_version.checksum),
+                (number, new_version),
+                |rehashed| hasher.hash_one(rehashed.1.checksum),
+            );
+        }
+        let skipped_new_lines = self.diagnostics.len() != num_diagnostics;
+
+        for line in old_lines.drain() {
+            let Some(old_version) = self.decode(&line, line.0, location)? else {
+                continue;
+            };
+            let new_version: Option<(usize, CrateVersion)> = new_versions
+                .find_entry(hasher.hash_one(old_version.checksum), |version| {
+                    version.1.checksum == old_version.checksum
+                })
+                .map(|entry| entry.remove().0)
+                .ok();
+            match new_version {
+                Some((_, new_version)) => {
+                    let change = match (old_version.yanked, new_version.yanked) {
+                        (true, false) => Change::Unyanked(new_version),
+                        (false, true) => Change::Yanked(new_version),
+                        _ if !is_equivalent(&old_version, &new_version) => Change::Modified {
+                            old: old_version.into(),
+                            new: new_version.into(),
+                        },
+                        _ => continue,
+                    };
+                    self.per_file_changes.push((line.0, change))
+                }
+                None if skipped_new_lines => {}
+                None => self
+                    .per_file_changes
+                    .push((line.0, Change::VersionDeleted(old_version))),
+            }
+        }
+        for (number, version) in new_versions.drain() {
+            let change = if version.yanked {
+                Change::AddedAndYanked(version)
+            } else {
+                Change::Added(version)
+            };
+            self.per_file_changes.push((number, change));
+        }
+        self.per_file_changes.sort_by_key(|t| t.0);
+        self.changes
+            .extend(self.per_file_changes.drain(..).map(|t| t.1));
+        Ok(())
+    }
+
+    /// Record the registry configuration as changed from `old` to `new`, with `None` meaning the file doesn't exist,
+    /// unless both decode to the same configuration.
+    ///
+    /// If the decode policy allows it, a configuration that can't be decoded is treated as if the file didn't exist.
+    pub fn config_changed(&mut self, old: Option<&[u8]>, new: Option<&[u8]>) -> Result<(), Error> {
+        let (old, new) = (self.decode_config(old)?, self.decode_config(new)?);
+        if old != new {
+            self.changes.push(Change::ConfigChanged { old, new });
+        }
+        Ok(())
+    }
+
+    /// Decode the registry configuration in `data`, or return `None` if there is none, or if it can't be decoded
+    /// and the decode policy allows to skip it.
+    fn decode_config(&mut self, data: Option<&[u8]>) -> Result<Option<RegistryConfig>, Error> {
+        let Some(data) = data else {
+            return Ok(None);
+        };
+        match (self.decode_policy, serde_json::from_slice(data)) {
+            (_, Ok(config)) => Ok(Some(config)),
+            (DecodePolicy::SkipAndReport, Err(error)) => {
+                self.diagnostics.push(DecodeDiagnostic {
+                    file_name: CONFIG_FILE_NAME.into(),
+                    line_number: 1,
+                    line: data.into(),
+                    error,
+                });
+                Ok(None)
+            }
+            (DecodePolicy::Strict, Err(err)) => Err(Error::ConfigDecode(err)),
+        }
+    }
+
+    /// Decode `line` with zero-based `number` in the file at `location`, or return `None` if it can't be decoded
+    /// and the decode policy allows to skip it.
+    fn decode(
+        &mut self,
+        line: &[u8],
+        number: usize,
+        location: &BStr,
+    ) -> Result<Option<CrateVersion>, Error> {
+        let err = match version_from_json_line(line, location) {
+            Ok(version) => return Ok(Some(version)),
+            Err(err) => err,
+        };
+        match (self.decode_policy, err) {
+            (
+                DecodePolicy::SkipAndReport,
+                Error::VersionDecode {
+                    source,
+                    file_name,
+                    line,
+                },
+            ) => {
+                self.diagnostics.push(DecodeDiagnostic {
+                    file_name,
+                    line_number: number + 1,
+                    line,
+                    error: source,
+                });
+                Ok(None)
+            }
+            (_, err) => Err(err),
+        }
+    }
+
+    /// Remove all changes recorded so far, in the order they were recorded in.
+    pub fn drain(&mut self) -> impl Iterator<Item = Change> + '_ {
+        self.changes.drain(..)
+    }
+
+    /// Remove all diagnostics recorded so far, in the order they were recorded in.
+    pub fn take_diagnostics(&mut self) -> Vec<DecodeDiagnostic> {
+        std::mem::take(&mut self.diagnostics)
+    }
+
     pub fn into_result(self) -> Result<Vec<Change>, Error> {
         match self.err {
             Some(err) => Err(err),
@@ -184,7 +314,63 @@ impl<'a> Deref for Line<'a> {
     }
 }
 
-fn version_from_json_line(line: &[u8], file_name: &BStr) -> Result<CrateVersion, Error> {
+/// Return `true` if `old` and `new` carry the same information, ignoring differences that are due to
+/// the index being normalized, like the order of dependencies and features, empty feature names and
+/// a missing dependency kind, which means `normal`.
+fn is_equivalent(old: &CrateVersion, new: &CrateVersion) -> bool {
+    fn features(features: &HashMap<String, Vec<String>>) -> Vec<(&String, Vec<&String>)> {
+        let mut features: Vec<_> = features
+            .iter()
+            .map(|(name, values)| {
+                let mut values: Vec<_> = values.iter().collect();
+                values.sort();
+                (name, values)
+            })
+            .collect();
+        features.sort();
+        features
+    }
+    fn dependencies(dependencies: &[Dependency]) -> Vec<Dependency> {
+        let mut dependencies: Vec<_> = dependencies
+            .iter()
+            .cloned()
+            .map(|mut dep| {
+                dep.features.retain(|feature| !feature.is_empty());
+                dep.features.sort();
+                dep.kind.get_or_insert(DependencyKind::Normal);
+                dep
+            })
+            .collect();
+        dependencies.sort();
+        dependencies
+    }
+    let CrateVersion {
+        name,
+        yanked,
+        version,
+        checksum,
+        features: old_features,
+        features2,
+        dependencies: old_dependencies,
+        schema_version,
+        links,
+        rust_version,
+        pubtime,
+    } = old;
+    *name == new.name
+        && *yanked == new.yanked
+        && *version == new.version
+        && *checksum == new.checksum
+        && *schema_version == new.schema_version
+        && *links == new.links
+        && *rust_version == new.rust_version
+        && *pubtime == new.pubtime
+        && features(old_features) == features(&new.features)
+        && features(features2) == features(&new.features2)
+        && dependencies(old_dependencies) == dependencies(&new.dependencies)
+}
+
+pub(crate) fn version_from_json_line(line: &[u8], file_name: &BStr) -> Result<CrateVersion, Error> {
     serde_json::from_slice(line).map_err(|err| Error::VersionDecode {
         source: err,
         file_name: file_name.into(),
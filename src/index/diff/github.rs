@@ -1,16 +1,18 @@
+use crate::index::diff::GithubFastPath;
+use reqwest::header::{HeaderMap, HeaderValue, ACCEPT, AUTHORIZATION, IF_NONE_MATCH, USER_AGENT};
 use reqwest::{StatusCode, Url};
 
-#[derive(Debug)]
+#[derive(Debug, Eq, PartialEq)]
 pub(crate) enum FastPath {
     UpToDate,
     NeedsFetch,
     Indeterminate,
 }
 
-/// extract username & repository from a fetch URL, only if it's on Github.
-fn user_and_repo_from_url_if_github(fetch_url: &gix::Url) -> Option<(String, String)> {
+/// extract username & repository from a fetch URL, only if it's on `host`, like `github.com`.
+fn user_and_repo_from_url_if_github(fetch_url: &gix::Url, host: &str) -> Option<(String, String)> {
     let url = Url::parse(&fetch_url.to_string()).ok()?;
-    if url.host_str() != Some("github.com") {
+    if url.host_str() != Some(host) {
         return None;
     }
 
@@ -34,6 +36,8 @@ fn user_and_repo_from_url_if_github(fetch_url: &gix::Url) -> Option<(String, Str
 /// actually run `git fetch`.
 ///
 /// On non-github fetch URLs we don't do anything and always run the fetch.
+/// The same is true if the fast-path is disabled in `options`, or if it fails and
+/// `options` don't make failures fatal.
 ///
 /// Code gotten and adapted from
 /// https://github.com/rust-lang/cargo/blob/edd36eba5e0d6e0cfcb84bd0cc651ba8bf5e7f83/src/cargo/sources/git/utils.rs#L1396
@@ -45,37 +49,126 @@ pub(crate) fn has_changes(
     fetch_url: &gix::Url,
     last_seen_reference: &gix::ObjectId,
     branch_name: &str,
+    options: &GithubFastPath,
 ) -> Result<FastPath, reqwest::Error> {
-    let (username, repository) = match user_and_repo_from_url_if_github(fetch_url) {
+    let url = match api_url(fetch_url, branch_name, options) {
         Some(url) => url,
         None => return Ok(FastPath::Indeterminate),
     };
 
-    let url = format!(
-        "https://api.github.com/repos/{}/{}/commits/{}",
-        username, repository, branch_name,
+    let res = blocking_client().and_then(|client| {
+        client
+            .get(&url)
+            .headers(headers(last_seen_reference, options))
+            .timeout(options.timeout)
+            .send()
+    });
+    handle_response(res.map(|response| response.status()), options)
+}
+
+/// Like [`has_changes()`], but without blocking.
+#[cfg(feature = "async")]
+pub(crate) async fn has_changes_async(
+    fetch_url: &gix::Url,
+    last_seen_reference: &gix::ObjectId,
+    branch_name: &str,
+    options: &GithubFastPath,
+) -> Result<FastPath, reqwest::Error> {
+    let url = match api_url(fetch_url, branch_name, options) {
+        Some(url) => url,
+        None => return Ok(FastPath::Indeterminate),
+    };
+
+    let res = match async_client() {
+        Ok(client) => {
+            client
+                .get(&url)
+                .headers(headers(last_seen_reference, options))
+                .timeout(options.timeout)
+                .send()
+                .await
+        }
+        Err(err) => Err(err),
+    };
+    handle_response(res.map(|response| response.status()), options)
+}
+
+/// Return a client shared by all fast-path requests, as creating one is costly.
+fn blocking_client() -> Result<reqwest::blocking::Client, reqwest::Error> {
+    static CLIENT: std::sync::OnceLock<reqwest::blocking::Client> = std::sync::OnceLock::new();
+    if let Some(client) = CLIENT.get() {
+        return Ok(client.clone());
+    }
+    let client = reqwest::blocking::Client::builder().build()?;
+    Ok(CLIENT.get_or_init(|| client).clone())
+}
+
+/// Like [`blocking_client()`], but for use in async code.
+///
+/// Unlike the blocking client, it can't be shared as its connections are bound to the runtime they were
+/// created in, and callers may drive their futures with more than one runtime.
+#[cfg(feature = "async")]
+fn async_client() -> Result<reqwest::Client, reqwest::Error> {
+    reqwest::Client::builder().build()
+}
+
+/// Return the URL of the API to learn the latest commit of `branch_name`, if the fast-path is enabled
+/// and `fetch_url` is served by it.
+fn api_url(fetch_url: &gix::Url, branch_name: &str, options: &GithubFastPath) -> Option<String> {
+    if !options.enabled {
+        return None;
+    }
+    let (username, repository) = user_and_repo_from_url_if_github(fetch_url, &options.host)?;
+    Some(format!(
+        "{}/repos/{}/{}/commits/{}",
+        options.api_url.trim_end_matches('/'),
+        username,
+        repository,
+        branch_name,
+    ))
+}
+
+fn headers(last_seen_reference: &gix::ObjectId, options: &GithubFastPath) -> HeaderMap {
+    let mut headers = HeaderMap::new();
+    headers.insert(USER_AGENT, HeaderValue::from_static("crates-index-diff"));
+    headers.insert(
+        ACCEPT,
+        HeaderValue::from_static("application/vnd.github.sha"),
+    );
+    headers.insert(
+        IF_NONE_MATCH,
+        HeaderValue::from_str(&format!("\"{}\"", last_seen_reference))
+            .expect("hex is a valid header value"),
     );
+    if let Some(value) = options
+        .token
+        .as_ref()
+        .and_then(|token| HeaderValue::from_str(&format!("Bearer {token}")).ok())
+    {
+        headers.insert(AUTHORIZATION, value);
+    }
+    headers
+}
 
-    let client = reqwest::blocking::Client::builder()
-        .user_agent("crates-index-diff")
-        .build()?;
-    let response = client
-        .get(&url)
-        .header("Accept", "application/vnd.github.sha")
-        .header("If-None-Match", format!("\"{}\"", last_seen_reference))
-        .send()?;
-
-    let status = response.status();
-    if status == StatusCode::NOT_MODIFIED {
-        Ok(FastPath::UpToDate)
+fn handle_response(
+    status: Result<StatusCode, reqwest::Error>,
+    options: &GithubFastPath,
+) -> Result<FastPath, reqwest::Error> {
+    let status = match status {
+        Ok(status) => status,
+        Err(_) if !options.fail_on_error => return Ok(FastPath::Indeterminate),
+        Err(err) => return Err(err),
+    };
+    Ok(if status == StatusCode::NOT_MODIFIED {
+        FastPath::UpToDate
     } else if status.is_success() {
-        Ok(FastPath::NeedsFetch)
+        FastPath::NeedsFetch
     } else {
         // Usually response_code == 404 if the repository does not exist, and
         // response_code == 422 if exists but GitHub is unable to resolve the
         // requested rev.
-        Ok(FastPath::Indeterminate)
-    }
+        FastPath::Indeterminate
+    })
 }
 
 #[cfg(test)]
@@ -87,6 +180,7 @@ mod tests {
     fn test_github_http_url() {
         let (user, repo) = user_and_repo_from_url_if_github(
             &gix::Url::try_from("https://github.com/some_user/some_repo.git").unwrap(),
+            "github.com",
         )
         .unwrap();
         assert_eq!(user, "some_user");
@@ -97,6 +191,7 @@ mod tests {
     fn test_github_ssh_url() {
         let (user, repo) = user_and_repo_from_url_if_github(
             &gix::Url::try_from("ssh://git@github.com/some_user/some_repo.git").unwrap(),
+            "github.com",
         )
         .unwrap();
         assert_eq!(user, "some_user");
@@ -107,7 +202,88 @@ mod tests {
     fn test_non_github_url() {
         assert!(user_and_repo_from_url_if_github(
             &gix::Url::try_from("https://not_github.com/some_user/some_repo.git").unwrap(),
+            "github.com",
         )
         .is_none());
     }
+
+    /// Serve a single request with `status_line` and return the URL to reach the server along with a handle
+    /// that yields the request.
+    fn serve_once(status_line: &'static str) -> (String, std::thread::JoinHandle<String>) {
+        use std::io::{Read, Write};
+        let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+        let url = format!("http://{}", listener.local_addr().unwrap());
+        let handle = std::thread::spawn(move || {
+            let (mut stream, _) = listener.accept().unwrap();
+            let mut request = Vec::new();
+            let mut buf = [0; 1024];
+            while !request.ends_with(b"\r\n\r\n") {
+                let n = stream.read(&mut buf).unwrap();
+                request.extend_from_slice(&buf[..n]);
+            }
+            write!(
+                stream,
+                "HTTP/1.1 {status_line}\r\nContent-Length: 0\r\nConnection: close\r\n\r\n"
+            )
+            .unwrap();
+            String::from_utf8(request).unwrap()
+        });
+        (url, handle)
+    }
+
+    #[test]
+    fn test_fast_path_options() {
+        let fetch_url = gix::Url::try_from("https://github.com/some_user/some_repo.git").unwrap();
+        let id = gix::ObjectId::null(gix::hash::Kind::Sha1);
+
+        let (api_url, server) = serve_once("304 Not Modified");
+        let options = GithubFastPath {
+            api_url,
+            token: Some("secret".into()),
+            ..Default::default()
+        };
+        assert_eq!(
+            has_changes(&fetch_url, &id, "main", &options).unwrap(),
+            FastPath::UpToDate
+        );
+        let request = server.join().unwrap().to_ascii_lowercase();
+        assert!(request.starts_with("get /repos/some_user/some_repo/commits/main "));
+        assert!(request.contains("authorization: bearer secret"));
+
+        let (api_url, server) = serve_once("200 OK");
+        let options = GithubFastPath {
+            api_url,
+            ..Default::default()
+        };
+        assert_eq!(
+            has_changes(&fetch_url, &id, "main", &options).unwrap(),
+            FastPath::NeedsFetch
+        );
+        server.join().unwrap();
+
+        let disabled = GithubFastPath {
+            enabled: false,
+            ..options.clone()
+        };
+        assert_eq!(
+            has_changes(&fetch_url, &id, "main", &disabled).unwrap(),
+            FastPath::Indeterminate,
+            "no request is made"
+        );
+
+        let unreachable = GithubFastPath {
+            api_url: options.api_url.clone(),
+            ..Default::default()
+        };
+        assert_eq!(
+            has_changes(&fetch_url, &id, "main", &unreachable).unwrap(),
+            FastPath::Indeterminate,
+            "failures aren't fatal by default"
+        );
+        let fatal = GithubFastPath {
+            fail_on_error: true,
+            ..unreachable
+        };
+        assert!(has_changes(&fetch_url, &id, "main", &fatal).is_err());
+    }
 }
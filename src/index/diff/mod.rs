@@ -2,12 +2,20 @@ use crate::{Change, Index};
 use bstr::ByteSlice;
 use gix::prelude::ObjectIdExt;
 use gix::traverse::commit::simple::CommitTimeOrder;
-use std::sync::atomic::AtomicBool;
+use std::collections::HashSet;
+use std::convert::TryFrom;
+use std::ops::ControlFlow;
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::sync::Arc;
 
-mod delegate;
+#[cfg(feature = "async")]
+mod asynchronous;
+pub(crate) mod delegate;
 mod github;
+#[cfg(feature = "parallel")]
+mod parallel;
 
-use delegate::Delegate;
+use delegate::{Delegate, CONFIG_FILE_NAME};
 
 /// The order we maintain for the produced changes.
 #[derive(Debug, Copy, Clone, Eq, PartialEq)]
@@ -27,6 +35,196 @@ pub enum Order {
     AsInCratesIndex,
 }
 
+/// Define where to start looking for changes if nothing was seen yet, i.e. if there is no
+/// [last seen reference](crate::Index::last_seen_reference()) or [cursor position](crate::index::cursor::Cursor::position()).
+#[derive(Debug, Copy, Clone, Default, Eq, PartialEq)]
+pub enum StartFrom {
+    /// Provide all changes since the beginning of history, i.e. one [`Change::Added`] for each version
+    /// that was ever published and still exists.
+    #[default]
+    Beginning,
+    /// Provide no changes at all, so that acknowledging the latest state marks it as seen.
+    ///
+    /// This allows new consumers to only learn about changes made from now on.
+    /// Use [`Index::fetch_snapshot_with_options()`] to obtain the state of the index as well.
+    Latest,
+}
+
+/// A commit of the crates index along with all changes it introduced.
+#[derive(Debug, Clone)]
+pub struct CommitChanges {
+    /// The id of the commit that introduced the `changes`.
+    pub id: gix::hash::ObjectId,
+    /// The time at which the commit was created, i.e. when the changes appeared in the index.
+    pub time: gix::date::Time,
+    /// The commit message, like ``Updating crate `gix#0.1.0` `` on crates.io.
+    pub message: bstr::BString,
+    /// All changes introduced by this commit.
+    pub changes: Vec<Change>,
+}
+
+/// Define what happens if a line in a crate file can't be decoded as [`CrateVersion`](crate::CrateVersion),
+/// or if the registry configuration can't be decoded as [`RegistryConfig`](crate::RegistryConfig).
+#[derive(Debug, Copy, Clone, Default, Eq, PartialEq)]
+pub enum DecodePolicy {
+    /// Fail the whole operation with [`Error::VersionDecode`] or [`Error::ConfigDecode`].
+    #[default]
+    Strict,
+    /// Skip the line as if it didn't exist and report it as [`DecodeDiagnostic`] in the [`Outcome`] of methods
+    /// like [`Index::peek_changes_with_outcome()`]. All other methods skip it silently.
+    ///
+    /// As a skipped line might hold a known version, no version of a crate file with skipped lines is reported
+    /// as deleted. A registry configuration that can't be decoded is reported as `None` in [`Change::ConfigChanged`].
+    SkipAndReport,
+}
+
+/// A line of a crate file that couldn't be decoded as [`CrateVersion`](crate::CrateVersion).
+///
+/// If the registry configuration couldn't be decoded, the file name is `config.json` and the line is
+/// its whole content, with line number 1.
+#[derive(Debug)]
+pub struct DecodeDiagnostic {
+    /// The name of the file the line is in, which is the name of the crate.
+    pub file_name: bstr::BString,
+    /// The number of the line in the file, starting at 1.
+    pub line_number: usize,
+    /// The line itself, without its newline.
+    pub line: bstr::BString,
+    /// The reason the line couldn't be decoded.
+    pub error: serde_json::Error,
+}
+
+/// The changes found by a single call, along with everything else that was learned while finding them.
+#[derive(Debug)]
+pub struct Outcome {
+    /// All changes that were found.
+    pub changes: Vec<Change>,
+    /// The order the changes are actually in, which is [`Order::ImplementationDefined`] if ordered changes were
+    /// requested but couldn't be provided, for instance after a [squash](Self::squash).
+    pub order: Order,
+    /// All lines that were skipped as they couldn't be decoded, which only happens with
+    /// [`DecodePolicy::SkipAndReport`].
+    pub diagnostics: Vec<DecodeDiagnostic>,
+    /// The squash of the history since the changes were last seen, if one was detected.
+    ///
+    /// This is only looked for if ordered changes were requested.
+    pub squash: Option<crate::index::squash::SquashDetected>,
+}
+
+/// Define which crates to look at when diffing, to avoid loading and parsing the files of all other crates.
+///
+/// Crates are matched by the name of their file in the index, which is their name in lower-case.
+#[derive(Clone, Default)]
+pub enum CrateFilter {
+    /// Look at all crates.
+    #[default]
+    All,
+    /// Only look at crates whose lower-case name is in the set.
+    ///
+    /// Use [`CrateFilter::names()`] to create it from names of any case.
+    Names(HashSet<String>),
+    /// Only look at crates whose lower-case name starts with the given lower-case prefix.
+    ///
+    /// Use [`CrateFilter::prefix()`] to create it from a prefix of any case.
+    Prefix(String),
+    /// Only look at crates for which the predicate returns `true` when called with the name of their file.
+    Predicate(Arc<dyn Fn(&bstr::BStr) -> bool + Send + Sync>),
+}
+
+impl CrateFilter {
+    /// Create a filter that only looks at the crates called `names`, which are matched case-insensitively.
+    pub fn names(names: impl IntoIterator<Item = impl AsRef<str>>) -> Self {
+        CrateFilter::Names(
+            names
+                .into_iter()
+                .map(|name| name.as_ref().to_ascii_lowercase())
+                .collect(),
+        )
+    }
+
+    /// Create a filter that only looks at crates whose name starts with `prefix`, which is matched case-insensitively.
+    pub fn prefix(prefix: impl AsRef<str>) -> Self {
+        CrateFilter::Prefix(prefix.as_ref().to_ascii_lowercase())
+    }
+
+    /// Create a filter that only looks at crates for which `predicate` returns `true` when called with the name
+    /// of their file.
+    pub fn predicate(predicate: impl Fn(&bstr::BStr) -> bool + Send + Sync + 'static) -> Self {
+        CrateFilter::Predicate(Arc::new(predicate))
+    }
+
+    /// Return `true` if the crate whose file is called `file_name` should be looked at.
+    pub fn matches(&self, file_name: &bstr::BStr) -> bool {
+        match self {
+            CrateFilter::All => true,
+            CrateFilter::Names(names) => file_name.to_str().is_ok_and(|name| names.contains(name)),
+            CrateFilter::Prefix(prefix) => file_name.starts_with(prefix.as_bytes()),
+            CrateFilter::Predicate(predicate) => predicate(file_name),
+        }
+    }
+}
+
+impl std::fmt::Debug for CrateFilter {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            CrateFilter::All => f.write_str("All"),
+            CrateFilter::Names(names) => f.debug_tuple("Names").field(names).finish(),
+            CrateFilter::Prefix(prefix) => f.debug_tuple("Prefix").field(prefix).finish(),
+            CrateFilter::Predicate(_) => f.write_str("Predicate(..)"),
+        }
+    }
+}
+
+/// Configure the GitHub fast-path, which asks the GitHub API if the fetched branch changed since the last seen commit,
+/// to avoid fetching if it didn't.
+///
+/// It's only used if the URL of the remote to fetch from is on [`host`](Self::host).
+#[derive(Clone)]
+pub struct GithubFastPath {
+    /// If `false`, the fast-path is never used and the remote is always fetched.
+    pub enabled: bool,
+    /// The base URL of the GitHub API, `https://api.github.com` by default.
+    ///
+    /// Change it to use GitHub Enterprise, like `https://github.example.com/api/v3`, or a mock server.
+    pub api_url: String,
+    /// The host of fetch URLs whose repositories are known to the API at [`api_url`](Self::api_url),
+    /// `github.com` by default.
+    pub host: String,
+    /// The token to authenticate with, which raises the rate limit of the API.
+    pub token: Option<String>,
+    /// The time after which a request to the API is considered failed, 30 seconds by default.
+    pub timeout: std::time::Duration,
+    /// If `true`, failed requests to the API fail the whole operation with [`Error::GithubFetch`].
+    /// Otherwise, which is the default, the remote is fetched as if the fast-path wasn't used.
+    pub fail_on_error: bool,
+}
+
+impl Default for GithubFastPath {
+    fn default() -> Self {
+        GithubFastPath {
+            enabled: true,
+            api_url: "https://api.github.com".into(),
+            host: "github.com".into(),
+            token: None,
+            timeout: std::time::Duration::from_secs(30),
+            fail_on_error: false,
+        }
+    }
+}
+
+impl std::fmt::Debug for GithubFastPath {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("GithubFastPath")
+            .field("enabled", &self.enabled)
+            .field("api_url", &self.api_url)
+            .field("host", &self.host)
+            .field("token", &self.token.as_ref().map(|_| "<redacted>"))
+            .field("timeout", &self.timeout)
+            .field("fail_on_error", &self.fail_on_error)
+            .finish()
+    }
+}
+
 /// The error returned by methods dealing with obtaining index changes.
 #[derive(Debug, thiserror::Error)]
 #[allow(missing_docs)]
@@ -35,6 +233,8 @@ pub enum Error {
     ReferenceEdit(#[from] Box<gix::reference::edit::Error>),
     #[error("Failed to parse rev-spec to determine which revisions to diff")]
     RevParse(#[from] Box<gix::revision::spec::parse::Error>),
+    #[error("Failed to parse rev-spec as single revision")]
+    RevParseSingle(#[from] Box<gix::revision::spec::parse::single::Error>),
     #[error(transparent)]
     DiffRewrites(#[from] Box<gix::diff::new_rewrites::Error>),
     #[error("Couldn't find blob that showed up when diffing trees")]
@@ -45,12 +245,49 @@ pub enum Error {
     Diff(#[from] Box<gix::diff::options::init::Error>),
     #[error(transparent)]
     DiffForEach(#[from] Box<gix::object::tree::diff::for_each::Error>),
+    #[error("The operation was interrupted")]
+    Interrupted,
+    #[error("Couldn't traverse the tree to find all crates")]
+    TraverseTree(#[from] Box<gix::traverse::tree::breadthfirst::Error>),
     #[error("Failed to decode {line:?} in file {file_name:?} as crate version")]
     VersionDecode {
         source: serde_json::Error,
         file_name: bstr::BString,
         line: bstr::BString,
     },
+    #[error("Failed to decode the registry configuration in config.json")]
+    ConfigDecode(#[source] serde_json::Error),
+    #[error("Couldn't obtain a commit to learn about its metadata")]
+    IntoCommit(#[from] Box<gix::object::try_into::Error>),
+    #[error("Couldn't decode the metadata of a commit")]
+    CommitMetadata(#[from] Box<gix::object::commit::Error>),
+    #[error("The cursor name {name:?} can't be used as part of a reference name")]
+    InvalidCursorName {
+        name: String,
+        source: gix::validate::reference::name::Error,
+    },
+    #[error("Expected {name:?} to be at {expected:?}, but it was at {actual:?}")]
+    AcknowledgeConflict {
+        name: gix::refs::FullName,
+        expected: Option<gix::hash::ObjectId>,
+        actual: Option<gix::hash::ObjectId>,
+    },
+    #[error(
+        "Refusing to move {name:?} from {from} to {to} as the latter doesn't contain the former"
+    )]
+    NonFastForward {
+        name: gix::refs::FullName,
+        from: gix::hash::ObjectId,
+        to: gix::hash::ObjectId,
+    },
+    #[error("The history of the index was squashed since {} was seen, and no snapshot branch is available to keep changes ordered", .0.last_seen)]
+    Squashed(Box<crate::index::squash::SquashDetected>),
+    #[error("Couldn't find out how two commits are related")]
+    MergeBase(#[from] Box<gix::repository::merge_base::Error>),
+    #[error("Couldn't list cursors")]
+    ListCursors(#[source] Box<dyn std::error::Error + Send + Sync + 'static>),
+    #[error("Couldn't list references to find snapshot branches")]
+    ListReferences(#[source] Box<dyn std::error::Error + Send + Sync + 'static>),
     #[error(transparent)]
     FindRemote(#[from] Box<gix::remote::find::existing::Error>),
     #[error(transparent)]
@@ -75,17 +312,22 @@ pub enum Error {
 
 impl_from_boxed!(gix::diff::new_rewrites::Error => Error::DiffRewrites);
 impl_from_boxed!(gix::diff::options::init::Error => Error::Diff);
+impl_from_boxed!(gix::object::commit::Error => Error::CommitMetadata);
 impl_from_boxed!(gix::object::find::existing::Error => Error::FindObject);
 impl_from_boxed!(gix::object::peel::to_kind::Error => Error::PeelToTree);
 impl_from_boxed!(gix::object::tree::diff::for_each::Error => Error::DiffForEach);
+impl_from_boxed!(gix::object::try_into::Error => Error::IntoCommit);
 impl_from_boxed!(gix::reference::edit::Error => Error::ReferenceEdit);
 impl_from_boxed!(gix::reference::find::existing::Error => Error::FindReference);
 impl_from_boxed!(gix::remote::connect::Error => Error::Connect);
+impl_from_boxed!(gix::repository::merge_base::Error => Error::MergeBase);
 impl_from_boxed!(gix::remote::fetch::Error => Error::Fetch);
 impl_from_boxed!(gix::remote::fetch::prepare::Error => Error::PrepareFetch);
 impl_from_boxed!(gix::remote::find::existing::Error => Error::FindRemote);
 impl_from_boxed!(gix::remote::init::Error => Error::InitAnonymousRemote);
 impl_from_boxed!(gix::revision::spec::parse::Error => Error::RevParse);
+impl_from_boxed!(gix::revision::spec::parse::single::Error => Error::RevParseSingle);
+impl_from_boxed!(gix::traverse::tree::breadthfirst::Error => Error::TraverseTree);
 
 /// Find changes without modifying the underling repository
 impl Index {
@@ -115,9 +357,18 @@ impl Index {
     /// The second field in the returned tuple is the commit object to which the changes were provided.
     /// If one would set the [`Self::last_seen_reference()`] to that object, the effect is exactly the same
     /// as if [`Self::fetch_changes()`] had been called.
+    /// If nothing was seen yet, [`Self::start_from()`] defines which changes are provided.
+    ///
+    /// If `order` is [`Order::AsInCratesIndex`] and the history was [squashed](Self::detect_squash()) since the last
+    /// seen commit, changes are provided through the [snapshot branch](crate::index::squash::SquashDetected::snapshot)
+    /// to keep them ordered.
+    /// If it wasn't fetched, changes are provided in implementation defined order instead, with the squash being
+    /// reported by [`Self::peek_changes_with_outcome()`], or [`Error::Squashed`] is returned if
+    /// [configured](crate::index::init::IndexBuilder::fail_on_squash()).
     ///
-    /// The `progress` and `should_interrupt` parameters are used to provide progress for fetches and allow
-    /// these operations to be interrupted gracefully.
+    /// The `progress` and `should_interrupt` parameters are used to provide progress for fetches and the diff
+    /// that follows, and allow these operations to be interrupted gracefully.
+    /// If the diff is interrupted, [`Error::Interrupted`] is returned.
     ///
     /// # Resource Usage
     ///
@@ -129,7 +380,7 @@ impl Index {
     // TODO: update this once it's clear how auto-gc works in `gitoxide`.
     pub fn peek_changes_with_options<P>(
         &self,
-        mut progress: P,
+        progress: P,
         should_interrupt: &AtomicBool,
         order: Order,
     ) -> Result<(Vec<Change>, gix::hash::ObjectId), Error>
@@ -137,105 +388,216 @@ impl Index {
         P: gix::NestedProgress,
         P::SubProgress: 'static,
     {
-        let repo = &self.repo;
-        let from = repo
-            .find_reference(self.seen_ref_name)
+        let (outcome, to) = self.peek_changes_with_outcome(progress, should_interrupt, order)?;
+        Ok((outcome.changes, to))
+    }
+
+    /// Like [`Self::peek_changes_with_options()`], but provides everything else that was learned while finding
+    /// the changes as well, like the lines that were [skipped](DecodePolicy::SkipAndReport).
+    pub fn peek_changes_with_outcome<P>(
+        &self,
+        mut progress: P,
+        should_interrupt: &AtomicBool,
+        order: Order,
+    ) -> Result<(Outcome, gix::hash::ObjectId), Error>
+    where
+        P: gix::NestedProgress,
+        P::SubProgress: 'static,
+    {
+        let from = self.last_seen_or_empty_tree();
+        let to = self.fetch_remote_inner(&mut progress, should_interrupt, Some(from))?;
+        let outcome = self.changes_between_commits_with_outcome(
+            self.start_of_changes(from, to),
+            to,
+            order,
+            progress.add_child("diff"),
+            should_interrupt,
+        )?;
+        Ok((outcome, to))
+    }
+
+    /// Fetch the remote just like [`Self::peek_changes_with_options()`] does, and return the commit the
+    /// fetched branch points to, without diffing anything.
+    ///
+    /// This allows to fetch once and learn about the changes of multiple [cursors](Self::cursor()).
+    pub fn fetch_remote<P>(
+        &self,
+        progress: P,
+        should_interrupt: &AtomicBool,
+    ) -> Result<gix::hash::ObjectId, Error>
+    where
+        P: gix::NestedProgress,
+        P::SubProgress: 'static,
+    {
+        self.fetch_remote_inner(progress, should_interrupt, None)
+    }
+
+    /// Return the commit [`Self::last_seen_reference()`] points to, or the empty tree if it doesn't exist yet.
+    pub(crate) fn last_seen_or_empty_tree(&self) -> gix::hash::ObjectId {
+        self.repo
+            .find_reference(self.seen_ref_name.as_str())
             .ok()
             .and_then(|r| r.try_id().map(|id| id.detach()))
-            .unwrap_or_else(|| gix::hash::ObjectId::empty_tree(repo.object_hash()));
-        let to = {
-            let mut remote = self
-                .remote_name
-                .as_deref()
-                .and_then(|name| {
-                    self.repo.find_remote(name.as_bstr()).ok().or_else(|| {
-                        self.repo
-                            .head()
-                            .ok()
-                            .and_then(|head| {
-                                head.into_remote(gix::remote::Direction::Fetch)
-                                    .and_then(|r| r.ok())
-                            })
-                            .or_else(|| {
-                                self.repo
-                                    .find_default_remote(gix::remote::Direction::Fetch)
-                                    .and_then(|r| r.ok())
-                            })
-                    })
-                })
-                .map(Ok)
-                .unwrap_or_else(|| {
+            .unwrap_or_else(|| gix::hash::ObjectId::empty_tree(self.repo.object_hash()))
+    }
+
+    /// Return the revision to diff from to see all changes up to `to`, which is `from`, or `to` itself if
+    /// `from` is the empty tree and changes should [start from the latest state](StartFrom::Latest).
+    pub(crate) fn start_of_changes(
+        &self,
+        from: gix::hash::ObjectId,
+        to: gix::hash::ObjectId,
+    ) -> gix::hash::ObjectId {
+        if self.start_from == StartFrom::Latest && from.is_empty_tree() {
+            to
+        } else {
+            from
+        }
+    }
+
+    /// Return the remote to fetch from, with a refspec for our branch if it doesn't have any.
+    pub(crate) fn remote(&self) -> Result<gix::Remote<'_>, Error> {
+        let mut remote = self
+            .remote_name
+            .as_deref()
+            .and_then(|name| {
+                self.repo.find_remote(name.as_bstr()).ok().or_else(|| {
                     self.repo
-                        .head()?
-                        .into_remote(gix::remote::Direction::Fetch)
-                        .map(|r| r.map_err(Error::from))
+                        .head()
+                        .ok()
+                        .and_then(|head| {
+                            head.into_remote(gix::remote::Direction::Fetch)
+                                .and_then(|r| r.ok())
+                        })
                         .or_else(|| {
                             self.repo
                                 .find_default_remote(gix::remote::Direction::Fetch)
-                                .map(|r| r.map_err(Error::from))
+                                .and_then(|r| r.ok())
                         })
-                        .unwrap_or_else(|| {
-                            self.repo
-                                .remote_at("https://github.com/rust-lang/crates.io-index")
-                                .map_err(Into::into)
-                        })
-                })?;
-            if remote.refspecs(gix::remote::Direction::Fetch).is_empty() {
-                let spec = format!(
-                    "+refs/heads/{branch}:refs/remotes/{remote}/{branch}",
-                    remote = self
-                        .remote_name
-                        .as_ref()
-                        .map(|n| n.as_bstr())
-                        .unwrap_or("origin".into()),
-                    branch = self.branch_name,
-                );
-                remote
-                    .replace_refspecs(Some(spec.as_str()), gix::remote::Direction::Fetch)
-                    .expect("valid statically known refspec");
-            }
+                })
+            })
+            .map(Ok)
+            .unwrap_or_else(|| {
+                self.repo
+                    .head()?
+                    .into_remote(gix::remote::Direction::Fetch)
+                    .map(|r| r.map_err(Error::from))
+                    .or_else(|| {
+                        self.repo
+                            .find_default_remote(gix::remote::Direction::Fetch)
+                            .map(|r| r.map_err(Error::from))
+                    })
+                    .unwrap_or_else(|| {
+                        self.repo
+                            .remote_at("https://github.com/rust-lang/crates.io-index")
+                            .map_err(Into::into)
+                    })
+            })?;
+        if remote.refspecs(gix::remote::Direction::Fetch).is_empty() {
+            let spec = format!(
+                "+refs/heads/{branch}:refs/remotes/{remote}/{branch}",
+                remote = self
+                    .remote_name
+                    .as_ref()
+                    .map(|n| n.as_bstr())
+                    .unwrap_or("origin".into()),
+                branch = self.branch_name,
+            );
+            remote
+                .replace_refspecs(Some(spec.as_str()), gix::remote::Direction::Fetch)
+                .expect("valid statically known refspec");
+        }
+        Ok(remote)
+    }
 
-            let (url, _) = remote.sanitized_url_and_version(gix::remote::Direction::Fetch)?;
+    /// Fetch the remote and return the commit its branch points to.
+    /// If `last_seen` is set, the GitHub fast-path is used to avoid fetching if it's still the latest commit.
+    pub(crate) fn fetch_remote_inner<P>(
+        &self,
+        mut progress: P,
+        should_interrupt: &AtomicBool,
+        last_seen: Option<gix::hash::ObjectId>,
+    ) -> Result<gix::hash::ObjectId, Error>
+    where
+        P: gix::NestedProgress,
+        P::SubProgress: 'static,
+    {
+        let remote = self.remote()?;
+        let (url, _) = remote.sanitized_url_and_version(gix::remote::Direction::Fetch)?;
+        if let Some(last_seen) = last_seen {
             if matches!(
-                github::has_changes(&url, &from, self.branch_name)?,
+                github::has_changes(&url, &last_seen, &self.branch_name, &self.github_fast_path)?,
                 github::FastPath::UpToDate
             ) {
-                from
-            } else {
-                let res: gix::remote::fetch::Outcome = remote
-                    .connect(gix::remote::Direction::Fetch)?
-                    .prepare_fetch(&mut progress, Default::default())?
-                    .receive(&mut progress, should_interrupt)?;
-                let branch_name = format!("refs/heads/{}", self.branch_name);
-                let local_tracking = res
-                    .ref_map
-                    .mappings
-                    .iter()
-                    .find_map(|m| match &m.remote {
-                        gix::remote::fetch::refmap::Source::Ref(r) => (r.unpack().0 == branch_name)
-                            .then_some(m.local.as_ref())
-                            .flatten(),
-                        _ => None,
-                    })
-                    .ok_or_else(|| Error::NoMatchingBranch {
-                        name: branch_name,
-                        mappings: res.ref_map.mappings.clone(),
-                    })?;
-                self.repo
-                    .find_reference(local_tracking)
-                    .expect("local tracking branch exists if we see it here")
-                    .id()
-                    .detach()
+                return Ok(last_seen);
             }
-        };
+        }
 
-        Ok((
-            match order {
-                Order::ImplementationDefined => self.changes_between_commits(from, to)?,
-                Order::AsInCratesIndex => self.changes_between_ancestor_commits(from, to)?.0,
-            },
-            to,
-        ))
+        let res: gix::remote::fetch::Outcome = remote
+            .connect(gix::remote::Direction::Fetch)?
+            .prepare_fetch(&mut progress, Default::default())?
+            .receive(&mut progress, should_interrupt)?;
+        let branch_name = format!("refs/heads/{}", self.branch_name);
+        let local_tracking = res
+            .ref_map
+            .mappings
+            .iter()
+            .find_map(|m| match &m.remote {
+                gix::remote::fetch::refmap::Source::Ref(r) => (r.unpack().0 == branch_name)
+                    .then_some(m.local.as_ref())
+                    .flatten(),
+                _ => None,
+            })
+            .ok_or_else(|| Error::NoMatchingBranch {
+                name: branch_name,
+                mappings: res.ref_map.mappings.clone(),
+            })?;
+        Ok(self
+            .repo
+            .find_reference(local_tracking)
+            .expect("local tracking branch exists if we see it here")
+            .id()
+            .detach())
+    }
+
+    /// Return the changes between `from` and `to` like [`Self::changes_between_commits_with_options()`] does, or like
+    /// [`Self::changes_between_ancestor_commits_with_options()`] does if `order` is [`Order::AsInCratesIndex`],
+    /// along with everything else that was learned while finding them.
+    ///
+    /// If the changes should be ordered and `from` isn't an ancestor of `to` as the history was
+    /// [squashed](Self::detect_squash()), the squash is diffed [through](Self::changes_through_squash()) and reported
+    /// in the outcome.
+    /// If this can't keep the changes ordered, [`Error::Squashed`] is returned if
+    /// [configured](crate::index::init::IndexBuilder::fail_on_squash()).
+    pub fn changes_between_commits_with_outcome(
+        &self,
+        from: impl Into<gix::hash::ObjectId>,
+        to: impl Into<gix::hash::ObjectId>,
+        order: Order,
+        progress: impl gix::Progress,
+        should_interrupt: &AtomicBool,
+    ) -> Result<Outcome, Error> {
+        let (from, to) = (from.into(), to.into());
+        if order == Order::ImplementationDefined {
+            return self.changes_between_commits_inner(from, to, progress, should_interrupt);
+        }
+        if let Some(commits) = self.commit_ancestry(from, to) {
+            return self.changes_in_commits(&commits, progress, should_interrupt);
+        }
+        match self.detect_squash(from, to)? {
+            Some(squash) => {
+                if squash.snapshot.is_none() && self.fail_on_squash {
+                    return Err(Error::Squashed(Box::new(squash)));
+                }
+                let mut outcome =
+                    self.changes_through_squash_inner(&squash, to, progress, should_interrupt)?;
+                outcome.squash = Some(squash);
+                Ok(outcome)
+            }
+            // The history wasn't squashed, but `from` couldn't be found by following the first parents of `to`
+            // in order of commit time.
+            None => self.changes_between_commits_inner(from, to, progress, should_interrupt),
+        }
     }
 
     /// Similar to [`Self::changes()`], but requires `from` and `to` objects to be provided. They may point
@@ -258,22 +620,118 @@ impl Index {
         from: impl Into<gix::hash::ObjectId>,
         to: impl Into<gix::hash::ObjectId>,
     ) -> Result<Vec<Change>, Error> {
-        let into_tree = |id: gix::hash::ObjectId| -> Result<gix::Tree<'_>, Error> {
-            Ok(id
-                .attach(&self.repo)
-                .object()?
-                .peel_to_kind(gix::object::Kind::Tree)?
-                .into_tree())
-        };
-        let from = into_tree(from.into())?;
-        let to = into_tree(to.into())?;
-        let mut delegate = Delegate::default();
-        from.changes()?
-            .options(|opts| {
-                opts.track_rewrites(None).track_filename();
-            })
-            .for_each_to_obtain_tree(&to, |change| delegate.handle(change))?;
-        delegate.into_result()
+        self.changes_between_commits_with_options(
+            from,
+            to,
+            gix::progress::Discard,
+            &AtomicBool::default(),
+        )
+    }
+
+    /// Like [`Self::changes_between_commits()`], but reports the amount of changed files as `progress` and
+    /// stops with [`Error::Interrupted`] once `should_interrupt` is set.
+    pub fn changes_between_commits_with_options(
+        &self,
+        from: impl Into<gix::hash::ObjectId>,
+        to: impl Into<gix::hash::ObjectId>,
+        progress: impl gix::Progress,
+        should_interrupt: &AtomicBool,
+    ) -> Result<Vec<Change>, Error> {
+        Ok(self
+            .changes_between_commits_inner(from.into(), to.into(), progress, should_interrupt)?
+            .changes)
+    }
+
+    pub(crate) fn changes_between_commits_inner(
+        &self,
+        from: gix::hash::ObjectId,
+        to: gix::hash::ObjectId,
+        mut progress: impl gix::Progress,
+        should_interrupt: &AtomicBool,
+    ) -> Result<Outcome, Error> {
+        progress.init(None, gix::progress::count("files"));
+        let start = std::time::Instant::now();
+        let mut changes = Vec::new();
+        let diagnostics = self.for_each_change_inner(
+            from,
+            to,
+            &progress.counter(),
+            should_interrupt,
+            |change| {
+                changes.push(change);
+                ControlFlow::Continue(())
+            },
+        )?;
+        progress.show_throughput(start);
+        Ok(Outcome {
+            changes,
+            order: Order::ImplementationDefined,
+            diagnostics,
+            squash: None,
+        })
+    }
+
+    /// Like [`Self::changes_between_commits()`], but instead of collecting all changes, each [`Change`] is passed
+    /// to `on_change` as soon as the crate file it belongs to was diffed.
+    ///
+    /// This keeps memory usage low even when diffing from the empty tree, which would otherwise produce millions
+    /// of changes. Return [`ControlFlow::Break`] from `on_change` to stop the diff early, without error.
+    ///
+    /// If the `parallel` feature is enabled and [`diff_threads`](crate::index::init::IndexBuilder::diff_threads()) permits, the top-level directories of the index are diffed
+    /// in parallel, while `on_change` is still called on the current thread.
+    ///
+    /// # Grouping and Ordering
+    ///
+    /// The same as in [`Self::changes_between_commits()`] applies.
+    pub fn for_each_change(
+        &self,
+        from: impl Into<gix::hash::ObjectId>,
+        to: impl Into<gix::hash::ObjectId>,
+        on_change: impl FnMut(Change) -> ControlFlow<()>,
+    ) -> Result<(), Error> {
+        self.for_each_change_inner(
+            from.into(),
+            to.into(),
+            &AtomicUsize::default(),
+            &AtomicBool::default(),
+            on_change,
+        )
+        .map(|_diagnostics| ())
+    }
+
+    /// Like [`Self::for_each_change()`], but count each changed file in `files` and return the lines
+    /// that were skipped.
+    fn for_each_change_inner(
+        &self,
+        from: gix::hash::ObjectId,
+        to: gix::hash::ObjectId,
+        files: &AtomicUsize,
+        should_interrupt: &AtomicBool,
+        mut on_change: impl FnMut(Change) -> ControlFlow<()>,
+    ) -> Result<Vec<DecodeDiagnostic>, Error> {
+        let from = into_tree(&self.repo, from)?;
+        let to = into_tree(&self.repo, to)?;
+        #[cfg(feature = "parallel")]
+        {
+            let threads = self.num_diff_threads();
+            if threads > 1 {
+                return self.for_each_change_in_parallel(
+                    from,
+                    to,
+                    threads,
+                    files,
+                    should_interrupt,
+                    on_change,
+                );
+            }
+        }
+        let mut delegate = Delegate::new(self.decode_policy, self.crate_filter.clone());
+        if for_each_config_change(&self.repo, &from, &to, files, &mut delegate, &mut on_change)?
+            .is_break()
+        {
+            return Ok(delegate.take_diagnostics());
+        }
+        for_each_change_in_trees(&from, &to, files, should_interrupt, delegate, on_change)
     }
 
     /// Similar to [`Self::changes()`], but requires `ancestor_commit` and `current_commit` objects to be provided
@@ -281,7 +739,8 @@ impl Index {
     ///
     /// If the invariants regarding `ancestor_commit` and `current_commit` are not upheld, we fallback
     /// to `changes_between_commits()` which doesn't have such restrictions.
-    /// This can happen if the crates-index was squashed for instance.
+    /// This can happen if the crates-index was squashed for instance, which can be detected explicitly
+    /// with [`Self::detect_squash()`].
     ///
     /// # Returns
     ///
@@ -301,26 +760,294 @@ impl Index {
         ancestor_commit: impl Into<gix::hash::ObjectId>,
         current_commit: impl Into<gix::hash::ObjectId>,
     ) -> Result<(Vec<Change>, Order), Error> {
+        self.changes_between_ancestor_commits_with_options(
+            ancestor_commit,
+            current_commit,
+            gix::progress::Discard,
+            &AtomicBool::default(),
+        )
+    }
+
+    /// Like [`Self::changes_between_ancestor_commits()`], but reports the amount of diffed commits as `progress`
+    /// and stops with [`Error::Interrupted`] once `should_interrupt` is set.
+    ///
+    /// If the invariants regarding `ancestor_commit` and `current_commit` are not upheld, the amount of changed files
+    /// is reported instead.
+    pub fn changes_between_ancestor_commits_with_options(
+        &self,
+        ancestor_commit: impl Into<gix::hash::ObjectId>,
+        current_commit: impl Into<gix::hash::ObjectId>,
+        progress: impl gix::Progress,
+        should_interrupt: &AtomicBool,
+    ) -> Result<(Vec<Change>, Order), Error> {
+        let outcome = self.changes_between_ancestor_commits_inner(
+            ancestor_commit.into(),
+            current_commit.into(),
+            progress,
+            should_interrupt,
+        )?;
+        Ok((outcome.changes, outcome.order))
+    }
+
+    pub(crate) fn changes_between_ancestor_commits_inner(
+        &self,
+        ancestor_commit: gix::hash::ObjectId,
+        current_commit: gix::hash::ObjectId,
+        progress: impl gix::Progress,
+        should_interrupt: &AtomicBool,
+    ) -> Result<Outcome, Error> {
+        match self.commit_ancestry(ancestor_commit, current_commit) {
+            Some(commits) => self.changes_in_commits(&commits, progress, should_interrupt),
+            None => self.changes_between_commits_inner(
+                ancestor_commit,
+                current_commit,
+                progress,
+                should_interrupt,
+            ),
+        }
+    }
+
+    /// Return the changes introduced by each commit in `commits` but the first, in order, reporting the amount of
+    /// diffed commits as `progress`.
+    fn changes_in_commits(
+        &self,
+        commits: &[gix::hash::ObjectId],
+        mut progress: impl gix::Progress,
+        should_interrupt: &AtomicBool,
+    ) -> Result<Outcome, Error> {
+        let start = std::time::Instant::now();
+        let mut changes = Vec::new();
+        let diagnostics =
+            self.for_each_change_in_commits(commits, &mut progress, should_interrupt, |change| {
+                changes.push(change);
+                ControlFlow::Continue(())
+            })?;
+        progress.show_throughput(start);
+        Ok(Outcome {
+            changes,
+            order: Order::AsInCratesIndex,
+            diagnostics,
+            squash: None,
+        })
+    }
+
+    /// Like [`Self::changes_between_ancestor_commits()`], but instead of collecting all changes, each [`Change`]
+    /// is passed to `on_change` as soon as it was observed, one commit at a time.
+    ///
+    /// Return [`ControlFlow::Break`] from `on_change` to stop early, without error.
+    /// The returned [`Order`] is the one the changes were actually provided in.
+    pub fn for_each_change_between_ancestor_commits(
+        &self,
+        ancestor_commit: impl Into<gix::hash::ObjectId>,
+        current_commit: impl Into<gix::hash::ObjectId>,
+        on_change: impl FnMut(Change) -> ControlFlow<()>,
+    ) -> Result<Order, Error> {
+        self.for_each_change_between_ancestor_commits_inner(
+            ancestor_commit.into(),
+            current_commit.into(),
+            &mut gix::progress::Discard,
+            &AtomicBool::default(),
+            on_change,
+        )
+        .map(|(order, _diagnostics)| order)
+    }
+
+    fn for_each_change_between_ancestor_commits_inner(
+        &self,
+        from_commit: gix::hash::ObjectId,
+        to_commit: gix::hash::ObjectId,
+        progress: &mut dyn gix::Progress,
+        should_interrupt: &AtomicBool,
+        on_change: impl FnMut(Change) -> ControlFlow<()>,
+    ) -> Result<(Order, Vec<DecodeDiagnostic>), Error> {
+        match self.commit_ancestry(from_commit, to_commit) {
+            Some(commits) => self
+                .for_each_change_in_commits(&commits, progress, should_interrupt, on_change)
+                .map(|diagnostics| (Order::AsInCratesIndex, diagnostics)),
+            None => {
+                progress.init(None, gix::progress::count("files"));
+                self.for_each_change_inner(
+                    from_commit,
+                    to_commit,
+                    &progress.counter(),
+                    should_interrupt,
+                    on_change,
+                )
+                .map(|diagnostics| (Order::ImplementationDefined, diagnostics))
+            }
+        }
+    }
+
+    /// Pass the changes introduced by each commit in `commits` but the first to `on_change`, in order, until it returns
+    /// [`ControlFlow::Break`], and report the amount of diffed commits as `progress`.
+    fn for_each_change_in_commits(
+        &self,
+        commits: &[gix::hash::ObjectId],
+        progress: &mut dyn gix::Progress,
+        should_interrupt: &AtomicBool,
+        mut on_change: impl FnMut(Change) -> ControlFlow<()>,
+    ) -> Result<Vec<DecodeDiagnostic>, Error> {
+        progress.init(
+            Some(commits.len().saturating_sub(1)),
+            gix::progress::count("commits"),
+        );
+        self.for_each_commit_changes(
+            commits,
+            &progress.counter(),
+            should_interrupt,
+            |_commit, changes| {
+                for change in changes {
+                    if on_change(change).is_break() {
+                        return Ok(ControlFlow::Break(()));
+                    }
+                }
+                Ok(ControlFlow::Continue(()))
+            },
+        )
+    }
+
+    /// Like [`Self::changes_between_ancestor_commits()`], but provides the changes grouped by the commit that
+    /// introduced them, along with the commit's id, time and message.
+    ///
+    /// This allows to learn when a change actually appeared in the index, instead of when it was observed.
+    ///
+    /// # Returns
+    ///
+    /// One entry per commit in `ancestor_commit..current_commit`, in the order they were made, along with the
+    /// `Order` the changes are actually in.
+    /// Commits without changes, like index normalizations, are included as well.
+    ///
+    /// If the invariants regarding `ancestor_commit` and `current_commit` are not upheld, all changes are
+    /// attributed to `current_commit`, which then must be a commit as well.
+    ///
+    /// Lines that were [skipped](DecodePolicy::SkipAndReport) aren't reported.
+    pub fn changes_between_ancestor_commits_with_metadata(
+        &self,
+        ancestor_commit: impl Into<gix::hash::ObjectId>,
+        current_commit: impl Into<gix::hash::ObjectId>,
+    ) -> Result<(Vec<CommitChanges>, Order), Error> {
+        self.changes_between_ancestor_commits_with_metadata_and_options(
+            ancestor_commit,
+            current_commit,
+            gix::progress::Discard,
+            &AtomicBool::default(),
+        )
+    }
+
+    /// Like [`Self::changes_between_ancestor_commits_with_metadata()`], but reports the amount of diffed commits
+    /// as `progress` and stops with [`Error::Interrupted`] once `should_interrupt` is set.
+    ///
+    /// If the invariants regarding `ancestor_commit` and `current_commit` are not upheld, the amount of changed files
+    /// is reported instead.
+    pub fn changes_between_ancestor_commits_with_metadata_and_options(
+        &self,
+        ancestor_commit: impl Into<gix::hash::ObjectId>,
+        current_commit: impl Into<gix::hash::ObjectId>,
+        mut progress: impl gix::Progress,
+        should_interrupt: &AtomicBool,
+    ) -> Result<(Vec<CommitChanges>, Order), Error> {
         let from_commit = ancestor_commit.into();
         let to_commit = current_commit.into();
         match self.commit_ancestry(from_commit, to_commit) {
             Some(commits) => {
-                let mut changes = Vec::new();
-                for from_to in commits.windows(2) {
-                    let from = from_to[0];
-                    let to = from_to[1];
-                    changes.extend(self.changes_between_commits(from, to)?);
-                }
-                Ok((changes, Order::AsInCratesIndex))
+                progress.init(
+                    Some(commits.len().saturating_sub(1)),
+                    gix::progress::count("commits"),
+                );
+                let start = std::time::Instant::now();
+                let mut out = Vec::with_capacity(commits.len().saturating_sub(1));
+                self.for_each_commit_changes(
+                    &commits,
+                    &progress.counter(),
+                    should_interrupt,
+                    |commit, changes| {
+                        out.push(self.commit_changes(commit, changes)?);
+                        Ok(ControlFlow::Continue(()))
+                    },
+                )?;
+                progress.show_throughput(start);
+                Ok((out, Order::AsInCratesIndex))
+            }
+            None => {
+                let changes = self.changes_between_commits_with_options(
+                    from_commit,
+                    to_commit,
+                    progress,
+                    should_interrupt,
+                )?;
+                Ok((
+                    vec![self.commit_changes(to_commit, changes)?],
+                    Order::ImplementationDefined,
+                ))
+            }
+        }
+    }
+
+    /// Call `on_commit` with each commit in `commits` but the first, along with the changes it introduced compared
+    /// to its predecessor in `commits`, in order, until it returns [`ControlFlow::Break`].
+    /// Each commit passed to `on_commit` is counted in `num_commits`.
+    ///
+    /// If the `parallel` feature is enabled and [`diff_threads`](crate::index::init::IndexBuilder::diff_threads()) permits, multiple commits
+    /// are diffed at the same time.
+    ///
+    /// Return the lines that were skipped in all commits that were diffed.
+    fn for_each_commit_changes(
+        &self,
+        commits: &[gix::hash::ObjectId],
+        num_commits: &AtomicUsize,
+        should_interrupt: &AtomicBool,
+        mut on_commit: impl FnMut(gix::hash::ObjectId, Vec<Change>) -> Result<ControlFlow<()>, Error>,
+    ) -> Result<Vec<DecodeDiagnostic>, Error> {
+        #[cfg(feature = "parallel")]
+        {
+            let threads = self.num_diff_threads();
+            if threads > 1 && commits.len() > 2 {
+                return self.for_each_commit_changes_in_parallel(
+                    commits,
+                    threads,
+                    num_commits,
+                    should_interrupt,
+                    on_commit,
+                );
+            }
+        }
+        let mut diagnostics = Vec::new();
+        for from_to in commits.windows(2) {
+            let mut changes = Vec::new();
+            diagnostics.extend(self.for_each_change_inner(
+                from_to[0],
+                from_to[1],
+                &AtomicUsize::default(),
+                should_interrupt,
+                |change| {
+                    changes.push(change);
+                    ControlFlow::Continue(())
+                },
+            )?);
+            num_commits.fetch_add(1, Ordering::Relaxed);
+            if on_commit(from_to[1], changes)?.is_break() {
+                break;
             }
-            None => self
-                .changes_between_commits(from_commit, to_commit)
-                .map(|c| (c, Order::ImplementationDefined)),
         }
+        Ok(diagnostics)
+    }
+
+    fn commit_changes(
+        &self,
+        id: gix::hash::ObjectId,
+        changes: Vec<Change>,
+    ) -> Result<CommitChanges, Error> {
+        let commit = id.attach(&self.repo).object()?.try_into_commit()?;
+        Ok(CommitChanges {
+            id,
+            time: commit.time()?,
+            message: commit.message_raw_sloppy().to_owned(),
+            changes,
+        })
     }
 
     /// Return a list of commits like `from_commit..=to_commits`.
-    fn commit_ancestry(
+    pub(crate) fn commit_ancestry(
         &self,
         ancestor_commit: gix::hash::ObjectId,
         current_commit: gix::hash::ObjectId,
@@ -363,6 +1090,108 @@ impl Index {
     }
 }
 
+/// Return the tree of the commit or tree `id`.
+fn into_tree(repo: &gix::Repository, id: gix::hash::ObjectId) -> Result<gix::Tree<'_>, Error> {
+    Ok(id
+        .attach(repo)
+        .object()?
+        .peel_to_kind(gix::object::Kind::Tree)?
+        .into_tree())
+}
+
+/// Pass the change of the registry configuration in the root of `from` and `to` to `on_change`, if there is one,
+/// and count it in `files`. `config.json` files anywhere else aren't the registry configuration and thus ignored.
+///
+/// The change is found by `delegate`, which keeps the diagnostics for the configuration if it can't be decoded.
+fn for_each_config_change(
+    repo: &gix::Repository,
+    from: &gix::Tree<'_>,
+    to: &gix::Tree<'_>,
+    files: &AtomicUsize,
+    delegate: &mut Delegate,
+    on_change: &mut impl FnMut(Change) -> ControlFlow<()>,
+) -> Result<ControlFlow<()>, Error> {
+    let config = |tree: &gix::Tree<'_>| {
+        tree.find_entry(CONFIG_FILE_NAME)
+            .filter(|entry| entry.mode().is_blob())
+            .map(|entry| entry.object_id())
+    };
+    let (old_config, new_config) = (config(from), config(to));
+    if old_config == new_config {
+        return Ok(ControlFlow::Continue(()));
+    }
+    files.fetch_add(1, Ordering::Relaxed);
+    let data = |id: Option<gix::hash::ObjectId>| -> Result<_, Error> {
+        Ok(match id {
+            Some(id) => Some(repo.find_object(id)?.detach().data),
+            None => None,
+        })
+    };
+    delegate.config_changed(data(old_config)?.as_deref(), data(new_config)?.as_deref())?;
+    for change in delegate.drain() {
+        if on_change(change).is_break() {
+            return Ok(ControlFlow::Break(()));
+        }
+    }
+    Ok(ControlFlow::Continue(()))
+}
+
+/// Diff `from` against `to` and pass each change to `on_change`, until it returns [`ControlFlow::Break`].
+/// Each changed file is counted in `files`, and [`Error::Interrupted`] is returned once `should_interrupt` is set.
+/// Changed files are turned into changes by `delegate`, and the lines it skipped are returned.
+fn for_each_change_in_trees(
+    from: &gix::Tree<'_>,
+    to: &gix::Tree<'_>,
+    files: &AtomicUsize,
+    should_interrupt: &AtomicBool,
+    mut delegate: Delegate,
+    mut on_change: impl FnMut(Change) -> ControlFlow<()>,
+) -> Result<Vec<DecodeDiagnostic>, Error> {
+    if should_interrupt.load(Ordering::Relaxed) {
+        return Err(Error::Interrupted);
+    }
+    let mut stopped = false;
+    let mut failed = None;
+    let res = from
+        .changes()?
+        .options(|opts| {
+            opts.track_rewrites(None).track_filename();
+        })
+        .for_each_to_obtain_tree(to, |change| {
+            if should_interrupt.load(Ordering::Relaxed) {
+                return Ok(gix::object::tree::diff::Action::Cancel);
+            }
+            files.fetch_add(1, Ordering::Relaxed);
+            let action = match delegate.handle(change) {
+                Ok(action) => action,
+                Err(err) => {
+                    // Keep the error as is instead of letting it be wrapped into a diff error.
+                    failed = Some(err);
+                    return Ok(gix::object::tree::diff::Action::Cancel);
+                }
+            };
+            for change in delegate.drain() {
+                if on_change(change).is_break() {
+                    stopped = true;
+                    return Ok(gix::object::tree::diff::Action::Cancel);
+                }
+            }
+            Ok::<_, Error>(action)
+        });
+    if let Some(err) = failed {
+        return Err(err);
+    }
+    match res {
+        // Cancelling the diff is reported as error, which we don't consider one as it was requested.
+        Err(_) if stopped => Ok(delegate.take_diagnostics()),
+        Err(_) if should_interrupt.load(Ordering::Relaxed) => Err(Error::Interrupted),
+        res => {
+            res?;
+            Ok(delegate.take_diagnostics())
+        }
+    }
+}
+
 /// Find changes while changing the underlying repository in one way or another.
 impl Index {
     /// As `fetch_changes_with_options()`, but without the options.
@@ -389,8 +1218,9 @@ impl Index {
     /// The [`Self::last_seen_reference()`] will be created or adjusted to point to the latest fetched
     /// state, which causes this method to have a different result each time it is called.
     ///
-    /// The `progress` and `should_interrupt` parameters are used to provide progress for fetches and allow
-    /// these operations to be interrupted gracefully.
+    /// The `progress` and `should_interrupt` parameters are used to provide progress for fetches and the diff
+    /// that follows, and allow these operations to be interrupted gracefully.
+    /// If the diff is interrupted, [`Error::Interrupted`] is returned.
     ///
     /// `order` configures how changes should be ordered.
     ///
@@ -411,16 +1241,35 @@ impl Index {
         P: gix::NestedProgress,
         P::SubProgress: 'static,
     {
-        let (changes, to) = self.peek_changes_with_options(progress, should_interrupt, order)?;
+        Ok(self
+            .fetch_changes_with_outcome(progress, should_interrupt, order)?
+            .changes)
+    }
+
+    /// Like [`Self::fetch_changes_with_options()`], but provides everything else that was learned while finding
+    /// the changes as well, like the lines that were [skipped](DecodePolicy::SkipAndReport).
+    pub fn fetch_changes_with_outcome<P>(
+        &self,
+        progress: P,
+        should_interrupt: &AtomicBool,
+        order: Order,
+    ) -> Result<Outcome, Error>
+    where
+        P: gix::NestedProgress,
+        P::SubProgress: 'static,
+    {
+        let (outcome, to) = self.peek_changes_with_outcome(progress, should_interrupt, order)?;
         self.set_last_seen_reference(to)?;
-        Ok(changes)
+        Ok(outcome)
     }
 
     /// Set the last seen reference to the given Oid. It will be created if it does not yet exists.
+    ///
+    /// Use [`Self::acknowledge()`] instead if multiple processes may update the last seen reference.
     pub fn set_last_seen_reference(&self, to: gix::hash::ObjectId) -> Result<(), Error> {
         let repo = self.repository();
         repo.reference(
-            self.seen_ref_name,
+            self.seen_ref_name.as_str(),
             to,
             gix::refs::transaction::PreviousValue::Any,
             "updating seen-ref head to latest fetched commit",
@@ -428,6 +1277,99 @@ impl Index {
         Ok(())
     }
 
+    /// Move the last seen reference to `to`, but only if it still points to `expected_previous`,
+    /// or doesn't exist if it is `None`.
+    /// This is typically the commit changes were [peeked](Self::peek_changes_with_options()) from, which was
+    /// obtained with [`Self::last_seen_reference()`] beforehand.
+    ///
+    /// If another process moved the reference in the meantime, [`Error::AcknowledgeConflict`] is returned
+    /// and nothing is changed, so the same changes aren't acknowledged twice and the reference never moves backwards.
+    /// If `to` doesn't contain `expected_previous` in its history, [`Error::NonFastForward`] is returned.
+    /// Use [`Self::acknowledge_forced()`] to allow this, for instance after the history was squashed.
+    pub fn acknowledge(
+        &self,
+        expected_previous: Option<gix::hash::ObjectId>,
+        to: gix::hash::ObjectId,
+    ) -> Result<(), Error> {
+        self.acknowledge_last_seen(expected_previous, to, false)
+    }
+
+    /// Like [`Self::acknowledge()`], but allows `to` to not contain `expected_previous` in its history.
+    pub fn acknowledge_forced(
+        &self,
+        expected_previous: Option<gix::hash::ObjectId>,
+        to: gix::hash::ObjectId,
+    ) -> Result<(), Error> {
+        self.acknowledge_last_seen(expected_previous, to, true)
+    }
+
+    fn acknowledge_last_seen(
+        &self,
+        expected_previous: Option<gix::hash::ObjectId>,
+        to: gix::hash::ObjectId,
+        force: bool,
+    ) -> Result<(), Error> {
+        let name = gix::refs::FullName::try_from(self.seen_ref_name.as_str())
+            .map_err(gix::reference::edit::Error::from)?;
+        self.acknowledge_inner(
+            name,
+            expected_previous,
+            to,
+            force,
+            "updating seen-ref head to latest fetched commit",
+        )
+    }
+
+    /// Move the reference `name` from `expected_previous` to `to` in a single transaction, without moving it
+    /// to a commit that doesn't contain `expected_previous` unless `force` is set.
+    pub(crate) fn acknowledge_inner(
+        &self,
+        name: gix::refs::FullName,
+        expected_previous: Option<gix::hash::ObjectId>,
+        to: gix::hash::ObjectId,
+        force: bool,
+        message: impl Into<bstr::BString>,
+    ) -> Result<(), Error> {
+        use gix::refs::transaction::PreviousValue;
+        let actual = || {
+            self.repo
+                .try_find_reference(name.as_ref())
+                .ok()
+                .flatten()
+                .and_then(|r| r.try_id().map(|id| id.detach()))
+        };
+        let conflict = |actual| Error::AcknowledgeConflict {
+            name: name.clone(),
+            expected: expected_previous,
+            actual,
+        };
+        let current = actual();
+        if current != expected_previous {
+            return Err(conflict(current));
+        }
+        let expected = match expected_previous {
+            Some(previous) => {
+                if !force && previous != to && self.commit_ancestry(previous, to).is_none() {
+                    return Err(Error::NonFastForward {
+                        name,
+                        from: previous,
+                        to,
+                    });
+                }
+                PreviousValue::MustExistAndMatch(gix::refs::Target::Object(previous))
+            }
+            None => PreviousValue::MustNotExist,
+        };
+        match self.repo.reference(name.as_ref(), to, expected, message) {
+            Ok(_) => Ok(()),
+            Err(err) => match actual() {
+                // Another process was faster, even though the reference was as expected when checking.
+                actual if actual != expected_previous => Err(conflict(actual)),
+                _ => Err(err.into()),
+            },
+        }
+    }
+
     /// Return all `CreateVersion`s observed between `from` and `to`. Both parameter are ref-specs
     /// pointing to either a commit or a tree.
     /// Learn more about specifying revisions
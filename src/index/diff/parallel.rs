@@ -0,0 +1,250 @@
+use crate::index::diff::delegate::Delegate;
+use crate::index::diff::{
+    for_each_change_in_trees, for_each_config_change, into_tree, DecodeDiagnostic, Error,
+};
+use crate::{Change, Index};
+use gix::prelude::ObjectIdExt;
+use std::collections::BTreeMap;
+use std::ops::ControlFlow;
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::sync::{mpsc, Condvar, Mutex};
+
+/// The amount of results that may be in flight between the diffing threads and the consumer.
+const CHANNEL_CAPACITY: usize = 1024;
+
+/// The amount of commit pairs per thread that may be diffed ahead of the pair whose changes are passed on next,
+/// which bounds the amount of changes that are kept in memory until they can be passed on in order.
+const PAIRS_AHEAD_PER_THREAD: usize = 4;
+
+/// A top-level directory of the index, like `1` or `ab`, which is diffed as a unit.
+#[derive(Default)]
+struct Partition {
+    old: Option<gix::hash::ObjectId>,
+    new: Option<gix::hash::ObjectId>,
+}
+
+impl Index {
+    /// Return the amount of threads to use for diffing, which is always at least 1.
+    pub(crate) fn num_diff_threads(&self) -> usize {
+        match self.diff_threads {
+            0 => std::thread::available_parallelism()
+                .map(Into::into)
+                .unwrap_or(1),
+            n => n,
+        }
+    }
+
+    /// Like [`Index::for_each_change()`], but diff each top-level directory of the trees `from` and `to` on one of
+    /// `threads` threads, passing all changes to `on_change` on the calling thread.
+    ///
+    /// All changes of a crate are found by the same thread, so their order is the same as when diffing on one thread.
+    /// Changes to the registry configuration are found on the calling thread before all others.
+    pub(crate) fn for_each_change_in_parallel(
+        &self,
+        from: gix::Tree<'_>,
+        to: gix::Tree<'_>,
+        threads: usize,
+        files: &AtomicUsize,
+        should_interrupt: &AtomicBool,
+        mut on_change: impl FnMut(Change) -> ControlFlow<()>,
+    ) -> Result<Vec<DecodeDiagnostic>, Error> {
+        let (decode_policy, crate_filter) = (self.decode_policy, &self.crate_filter);
+        let mut delegate = Delegate::new(decode_policy, crate_filter.clone());
+        let config_flow =
+            for_each_config_change(&self.repo, &from, &to, files, &mut delegate, &mut on_change)?;
+        let diagnostics = Mutex::new(delegate.take_diagnostics());
+        if config_flow.is_break() {
+            return Ok(diagnostics.into_inner().expect("not poisoned"));
+        }
+
+        let partitions = partitions(&from, &to);
+        in_parallel(
+            &self.repo,
+            partitions.len(),
+            threads,
+            |repo, partition, tx, should_stop| {
+                let Partition { old, new } = &partitions[partition];
+                let into_tree = |id: Option<gix::hash::ObjectId>| -> Result<gix::Tree<'_>, Error> {
+                    Ok(match id {
+                        Some(id) => id.attach(repo).object()?.into_tree(),
+                        None => repo.empty_tree(),
+                    })
+                };
+                let skipped = for_each_change_in_trees(
+                    &into_tree(*old)?,
+                    &into_tree(*new)?,
+                    files,
+                    should_interrupt,
+                    Delegate::new(decode_policy, crate_filter.clone()),
+                    |change| {
+                        if should_stop.load(Ordering::Relaxed) || tx.send(Ok(change)).is_err() {
+                            ControlFlow::Break(())
+                        } else {
+                            ControlFlow::Continue(())
+                        }
+                    },
+                )?;
+                diagnostics.lock().expect("not poisoned").extend(skipped);
+                Ok(())
+            },
+            |change| Ok(on_change(change)),
+        )?;
+        Ok(diagnostics.into_inner().expect("not poisoned"))
+    }
+
+    /// Like [`Index::for_each_commit_changes()`], but diff up to `threads` pairs of commits at the same time,
+    /// while still calling `on_commit` in the order of `commits` on the calling thread.
+    ///
+    /// Threads don't diff more than [`PAIRS_AHEAD_PER_THREAD`] pairs each ahead of the pair that is passed on next,
+    /// so slow pairs don't cause the changes of all later pairs to pile up.
+    pub(crate) fn for_each_commit_changes_in_parallel(
+        &self,
+        commits: &[gix::hash::ObjectId],
+        threads: usize,
+        num_commits: &AtomicUsize,
+        should_interrupt: &AtomicBool,
+        mut on_commit: impl FnMut(gix::hash::ObjectId, Vec<Change>) -> Result<ControlFlow<()>, Error>,
+    ) -> Result<Vec<DecodeDiagnostic>, Error> {
+        let pairs: Vec<_> = commits.windows(2).collect();
+        let (decode_policy, crate_filter) = (self.decode_policy, &self.crate_filter);
+        let mut diagnostics = Vec::new();
+        let max_pairs_ahead = threads * PAIRS_AHEAD_PER_THREAD;
+        // The pair whose changes are passed on next, to let threads wait until they may diff their pair.
+        let next_pair = (Mutex::new(0), Condvar::new());
+        let mut out_of_order = BTreeMap::new();
+        in_parallel(
+            &self.repo,
+            pairs.len(),
+            threads,
+            |repo, pair, tx, should_stop| {
+                let (lock, pair_passed_on) = &next_pair;
+                let mut next = lock.lock().expect("not poisoned");
+                while pair >= *next + max_pairs_ahead && !should_stop.load(Ordering::Relaxed) {
+                    // Time out to notice `should_stop`, which is set without notifying us.
+                    next = pair_passed_on
+                        .wait_timeout(next, std::time::Duration::from_millis(50))
+                        .expect("not poisoned")
+                        .0;
+                }
+                drop(next);
+
+                let (from, to) = (pairs[pair][0], pairs[pair][1]);
+                let mut changes = Vec::new();
+                let skipped = for_each_change_in_trees(
+                    &into_tree(repo, from)?,
+                    &into_tree(repo, to)?,
+                    &AtomicUsize::default(),
+                    should_interrupt,
+                    Delegate::new(decode_policy, crate_filter.clone()),
+                    |change| {
+                        if should_stop.load(Ordering::Relaxed) {
+                            return ControlFlow::Break(());
+                        }
+                        changes.push(change);
+                        ControlFlow::Continue(())
+                    },
+                )?;
+                if !should_stop.load(Ordering::Relaxed) {
+                    tx.send(Ok((pair, (changes, skipped)))).ok();
+                }
+                Ok(())
+            },
+            |(pair, changes_and_skipped)| {
+                out_of_order.insert(pair, changes_and_skipped);
+                let (lock, pair_passed_on) = &next_pair;
+                let mut next = *lock.lock().expect("not poisoned");
+                while let Some((changes, skipped)) = out_of_order.remove(&next) {
+                    diagnostics.extend(skipped);
+                    num_commits.fetch_add(1, Ordering::Relaxed);
+                    if on_commit(pairs[next][1], changes)?.is_break() {
+                        return Ok(ControlFlow::Break(()));
+                    }
+                    next += 1;
+                    *lock.lock().expect("not poisoned") = next;
+                    pair_passed_on.notify_all();
+                }
+                Ok(ControlFlow::Continue(()))
+            },
+        )?;
+        Ok(diagnostics)
+    }
+}
+
+/// Run `work` for each job in `0..num_jobs` on up to `threads` threads, each with its own instance of `repo`, and pass
+/// everything it sends to `consume` on the calling thread until it returns [`ControlFlow::Break`] or fails.
+///
+/// `work` is expected to stop early once its `should_stop` flag is set.
+fn in_parallel<T: Send>(
+    repo: &gix::Repository,
+    num_jobs: usize,
+    threads: usize,
+    work: impl Fn(
+            &gix::Repository,
+            usize,
+            &mpsc::SyncSender<Result<T, Error>>,
+            &AtomicBool,
+        ) -> Result<(), Error>
+        + Sync,
+    mut consume: impl FnMut(T) -> Result<ControlFlow<()>, Error>,
+) -> Result<(), Error> {
+    let repo = repo.clone().into_sync();
+    let next_job = AtomicUsize::default();
+    let should_stop = AtomicBool::default();
+    std::thread::scope(|scope| {
+        let (tx, rx) = mpsc::sync_channel(CHANNEL_CAPACITY);
+        for _ in 0..threads.min(num_jobs) {
+            let tx = tx.clone();
+            let (repo, work, next_job, should_stop) = (&repo, &work, &next_job, &should_stop);
+            scope.spawn(move || {
+                let mut repo = repo.to_thread_local();
+                repo.object_cache_size_if_unset(4 * 1024 * 1024);
+                loop {
+                    let job = next_job.fetch_add(1, Ordering::Relaxed);
+                    if job >= num_jobs || should_stop.load(Ordering::Relaxed) {
+                        break;
+                    }
+                    if let Err(err) = work(&repo, job, &tx, should_stop) {
+                        should_stop.store(true, Ordering::Relaxed);
+                        tx.send(Err(err)).ok();
+                        break;
+                    }
+                }
+            });
+        }
+        drop(tx);
+
+        let res = rx.into_iter().try_for_each(|item| match consume(item?)? {
+            ControlFlow::Continue(()) => Ok(()),
+            ControlFlow::Break(()) => Err(None),
+        });
+        // Dropping the receiver makes the threads stop as soon as they try to send, but they may be busy until then.
+        should_stop.store(true, Ordering::Relaxed);
+        match res {
+            Ok(()) | Err(None) => Ok(()),
+            Err(Some(err)) => Err(err),
+        }
+    })
+}
+
+/// Return all top-level directories that differ between `from` and `to`.
+fn partitions(from: &gix::Tree<'_>, to: &gix::Tree<'_>) -> Vec<Partition> {
+    fn directories<'a>(
+        tree: &'a gix::Tree<'_>,
+    ) -> impl Iterator<Item = (bstr::BString, gix::hash::ObjectId)> + 'a {
+        tree.iter()
+            .filter_map(Result::ok)
+            .filter(|entry| entry.mode().is_tree())
+            .map(|entry| (entry.filename().to_owned(), entry.object_id()))
+    }
+    let mut partitions = BTreeMap::<_, Partition>::new();
+    for (name, id) in directories(from) {
+        partitions.entry(name).or_default().old = Some(id);
+    }
+    for (name, id) in directories(to) {
+        partitions.entry(name).or_default().new = Some(id);
+    }
+    partitions
+        .into_values()
+        .filter(|partition| partition.old != partition.new)
+        .collect()
+}
@@ -1,7 +1,9 @@
-use crate::index::{CloneOptions, LAST_SEEN_REFNAME};
+use crate::index::diff::{CrateFilter, DecodePolicy, GithubFastPath, StartFrom};
+use crate::index::{CloneOptions, INDEX_GIT_URL, LAST_SEEN_REFNAME};
 use crate::Index;
+use bstr::{BString, ByteSlice};
 use std::borrow::Cow;
-use std::path::Path;
+use std::path::{Path, PathBuf};
 use std::sync::atomic::AtomicBool;
 
 /// The error returned by various initialization methods.
@@ -14,30 +16,299 @@ pub enum Error {
     Fetch(#[from] Box<gix::clone::fetch::Error>),
     #[error(transparent)]
     Open(#[from] Box<gix::open::Error>),
+    #[error("Only one of `depth` and `shallow_since` may be set when cloning")]
+    ConflictingShallowOptions,
 }
 
 impl_from_boxed!(gix::clone::Error => Error::PrepareClone);
 impl_from_boxed!(gix::clone::fetch::Error => Error::Fetch);
 impl_from_boxed!(gix::open::Error => Error::Open);
 
+/// Configure how an [`Index`] is opened, or cloned if it doesn't exist yet.
+///
+/// Obtain one with [`Index::builder()`].
+#[derive(Debug, Clone)]
+pub struct IndexBuilder {
+    path: PathBuf,
+    url: String,
+    remote_name: Option<BString>,
+    branch_name: Option<String>,
+    seen_ref_name: String,
+    object_cache_size: Option<usize>,
+    github_fast_path: GithubFastPath,
+    shallow: gix::remote::fetch::Shallow,
+    diff_threads: usize,
+    decode_policy: DecodePolicy,
+    crate_filter: CrateFilter,
+    start_from: StartFrom,
+    fail_on_squash: bool,
+}
+
+impl IndexBuilder {
+    /// The URL to clone the index from if it doesn't exist yet, which is the official crates.io index by default.
+    pub fn url(mut self, url: impl Into<String>) -> Self {
+        self.url = url.into();
+        self
+    }
+
+    /// If the index is cloned, only clone this amount of commits, with `1` meaning only the latest one,
+    /// instead of the complete history.
+    ///
+    /// Changes can be obtained as usual afterwards, but only within the history that was cloned.
+    /// This replaces a cutoff set with [`Self::shallow_since()`].
+    pub fn depth(mut self, depth: std::num::NonZeroU32) -> Self {
+        self.shallow = gix::remote::fetch::Shallow::DepthAtRemote(depth);
+        self
+    }
+
+    /// If the index is cloned, only clone commits made after `cutoff`, instead of the complete history.
+    ///
+    /// Changes can be obtained as usual afterwards, but only within the history that was cloned.
+    /// This replaces a depth set with [`Self::depth()`].
+    pub fn shallow_since(mut self, cutoff: gix::date::Time) -> Self {
+        self.shallow = gix::remote::fetch::Shallow::Since { cutoff };
+        self
+    }
+
+    /// The name of the remote to fetch from, instead of the first configured remote.
+    pub fn remote_name(mut self, name: impl Into<BString>) -> Self {
+        self.remote_name = Some(name.into());
+        self
+    }
+
+    /// The name of the branch to fetch, like `master`.
+    ///
+    /// If unset, it's the [default branch](Index::default_branch_name()) of the remote, or `master` if it
+    /// can't be detected.
+    /// If it's set to a branch other than the default one, this is reported as `progress` info when building.
+    pub fn branch_name(mut self, name: impl Into<String>) -> Self {
+        self.branch_name = Some(name.into());
+        self
+    }
+
+    /// The name and path of the reference used to keep track of the last seen state of the index,
+    /// which is `refs/heads/crates-index-diff_last-seen` by default.
+    pub fn seen_ref_name(mut self, name: impl Into<String>) -> Self {
+        self.seen_ref_name = name.into();
+        self
+    }
+
+    /// The amount of bytes to use for caching objects, overriding the git configuration.
+    ///
+    /// If unset, 4MB are used unless the git configuration says otherwise.
+    pub fn object_cache_size(mut self, bytes: usize) -> Self {
+        self.object_cache_size = Some(bytes);
+        self
+    }
+
+    /// How to use the GitHub API to avoid fetching if nothing changed.
+    pub fn github_fast_path(mut self, options: GithubFastPath) -> Self {
+        self.github_fast_path = options;
+        self
+    }
+
+    /// The amount of threads to use when diffing trees, with `0` meaning one thread per logical core and `1`
+    /// disabling parallelism. It's only used if the `parallel` feature is enabled.
+    ///
+    /// It defaults to `0` if the `max-performance` or `max-performance-safe` feature is enabled, and to `1` otherwise.
+    pub fn diff_threads(mut self, threads: usize) -> Self {
+        self.diff_threads = threads;
+        self
+    }
+
+    /// What to do if a line in a crate file can't be decoded, which is failing the operation by default.
+    pub fn decode_policy(mut self, policy: DecodePolicy) -> Self {
+        self.decode_policy = policy;
+        self
+    }
+
+    /// The crates to look at when diffing, with all other crates being ignored before their files are loaded.
+    /// All crates are looked at by default.
+    pub fn crate_filter(mut self, filter: CrateFilter) -> Self {
+        self.crate_filter = filter;
+        self
+    }
+
+    /// Where to start looking for changes if nothing was seen yet, which is the beginning of history by default.
+    pub fn start_from(mut self, start_from: StartFrom) -> Self {
+        self.start_from = start_from;
+        self
+    }
+
+    /// If `true`, [ordered](crate::index::diff::Order::AsInCratesIndex) changes fail with
+    /// [`Error::Squashed`](crate::index::diff::Error::Squashed) if the history was squashed since they were last seen
+    /// and no snapshot branch is available to keep them ordered.
+    ///
+    /// Otherwise, which is the default, they are provided in implementation defined order instead, and the squash is
+    /// reported in the [`Outcome`](crate::index::diff::Outcome).
+    pub fn fail_on_squash(mut self, fail: bool) -> Self {
+        self.fail_on_squash = fail;
+        self
+    }
+
+    /// Like [`Self::build_with_options()`], but without progress and without a way to interrupt the clone.
+    pub fn build(self) -> Result<Index, Error> {
+        self.build_with_options(gix::progress::Discard, &AtomicBool::default())
+    }
+
+    /// Open the index, or clone it while reporting `progress`, until `should_interrupt` is set.
+    ///
+    /// The clone contains all of the history unless it is limited by [`CloneOptions::depth`] or
+    /// [`CloneOptions::shallow_since`].
+    pub fn build_with_options<P>(
+        self,
+        mut progress: P,
+        should_interrupt: &AtomicBool,
+    ) -> Result<Index, Error>
+    where
+        P: gix::NestedProgress,
+        P::SubProgress: 'static,
+    {
+        let IndexBuilder {
+            path,
+            url,
+            remote_name,
+            branch_name,
+            seen_ref_name,
+            object_cache_size,
+            github_fast_path,
+            shallow,
+            diff_threads,
+            decode_policy,
+            crate_filter,
+            start_from,
+            fail_on_squash,
+        } = self;
+        let mut repo = match gix::open(&path) {
+            Ok(repo) => repo,
+            Err(gix::open::Error::NotARepository { .. }) => {
+                let (repo, _out) = gix::prepare_clone_bare(url, &path)?
+                    .with_shallow(shallow)
+                    .fetch_only(&mut progress, should_interrupt)?;
+                repo
+            }
+            Err(err) => return Err(err.into()),
+        };
+
+        match object_cache_size {
+            Some(bytes) => repo.object_cache_size(bytes),
+            None => repo.object_cache_size_if_unset(4 * 1024 * 1024),
+        }
+        let remote_name =
+            remote_name.or_else(|| repo.remote_names().into_iter().next().map(Cow::into_owned));
+        let default_branch_name = default_branch_name(&repo, remote_name.as_ref());
+        let branch_name = match (branch_name, &default_branch_name) {
+            (Some(configured), Some(detected)) if configured != *detected => {
+                progress.info(format!(
+                    "Fetching configured branch '{configured}' instead of the default branch '{detected}' of the remote"
+                ));
+                configured
+            }
+            (Some(configured), _) => configured,
+            (None, Some(detected)) => detected.clone(),
+            (None, None) => "master".into(),
+        };
+        Ok(Index {
+            repo,
+            remote_name,
+            branch_name,
+            default_branch_name,
+            seen_ref_name,
+            diff_threads,
+            decode_policy,
+            crate_filter,
+            github_fast_path,
+            start_from,
+            fail_on_squash,
+        })
+    }
+}
+
+/// Return the short name of the default branch of the remote called `remote_name`, or `origin`,
+/// as stored in `refs/remotes/<remote>/HEAD`, or the branch `HEAD` points to.
+///
+/// The latter is the branch the remote advertised as its `HEAD` when cloning.
+fn default_branch_name(repo: &gix::Repository, remote_name: Option<&BString>) -> Option<String> {
+    let remote = remote_name.map_or("origin".into(), |name| name.to_string());
+    let remote_prefix = format!("refs/remotes/{remote}/");
+    let from_remote_head = repo
+        .find_reference(format!("{remote_prefix}HEAD").as_str())
+        .ok()
+        .and_then(|head| match head.target() {
+            gix::refs::TargetRef::Symbolic(name) => name
+                .as_bstr()
+                .strip_prefix(remote_prefix.as_bytes())
+                .map(|name| name.to_str_lossy().into_owned()),
+            gix::refs::TargetRef::Object(_) => None,
+        });
+    from_remote_head.or_else(|| {
+        let head = repo.head_name().ok()??;
+        let (category, name) = head.category_and_short_name()?;
+        (category == gix::refs::Category::LocalBranch).then(|| name.to_string())
+    })
+}
+
 /// Initialization
 impl Index {
+    /// Return a builder to open the index at `path`, or to clone it there if it doesn't exist yet.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// use crates_index_diff::Index;
+    ///
+    /// # let path = tempdir::TempDir::new("index").unwrap();
+    /// let index = Index::builder(path.path())
+    ///     .url("https://github.com/rust-lang/staging.crates.io-index")
+    ///     .seen_ref_name("refs/heads/my-last-seen")
+    ///     .build()?;
+    /// # Ok::<(), crates_index_diff::index::init::Error>(())
+    /// ```
+    pub fn builder(path: impl Into<PathBuf>) -> IndexBuilder {
+        IndexBuilder {
+            path: path.into(),
+            url: INDEX_GIT_URL.into(),
+            remote_name: None,
+            branch_name: None,
+            seen_ref_name: LAST_SEEN_REFNAME.into(),
+            object_cache_size: None,
+            github_fast_path: Default::default(),
+            shallow: Default::default(),
+            diff_threads: if cfg!(any(
+                feature = "max-performance",
+                feature = "max-performance-safe"
+            )) {
+                0
+            } else {
+                1
+            },
+            decode_policy: Default::default(),
+            crate_filter: Default::default(),
+            start_from: Default::default(),
+            fail_on_squash: false,
+        }
+    }
+
     /// Return a new `Index` instance from the given `path`, which should contain a bare clone of the `crates.io` index.
     /// If the directory does not contain the repository or does not exist, it will be cloned from
-    /// the official location automatically (with complete history).
+    /// the location in `options` automatically, with complete history unless `options` limit it.
     ///
     /// An error will occour if the repository exists and the remote URL does not match the given repository URL.
     ///
+    /// The branch to fetch is the [default branch](Index::default_branch_name()) of the remote, use
+    /// [`Index::builder()`] to configure another one.
+    ///
     /// # Examples
     ///
     /// ```no_run
     /// use std::sync::atomic::AtomicBool;
-    /// use crates_index_diff::{Index, index, git};
+    /// use crates_index_diff::{Index, index, gix};
     ///
     /// # let path = tempdir::TempDir::new("index").unwrap();
     /// // Note that credentials are automatically picked up from the standard git configuration.
     /// let mut options = index::CloneOptions {
     ///   url: "https://github.com/rust-lang/staging.crates.io-index".into(),
+    ///   ..Default::default()
     /// };
     ///
     ///
@@ -48,31 +319,24 @@ impl Index {
         path: impl AsRef<Path>,
         progress: P,
         should_interrupt: &AtomicBool,
-        CloneOptions { url }: CloneOptions,
+        CloneOptions {
+            url,
+            depth,
+            shallow_since,
+        }: CloneOptions,
     ) -> Result<Index, Error>
     where
         P: gix::NestedProgress,
         P::SubProgress: 'static,
     {
-        let path = path.as_ref();
-        let mut repo = match gix::open(path) {
-            Ok(repo) => repo,
-            Err(gix::open::Error::NotARepository { .. }) => {
-                let (repo, _out) =
-                    gix::prepare_clone_bare(url, path)?.fetch_only(progress, should_interrupt)?;
-                repo
-            }
-            Err(err) => return Err(err.into()),
+        let builder = Index::builder(path.as_ref()).url(url);
+        let builder = match (depth, shallow_since) {
+            (Some(_), Some(_)) => return Err(Error::ConflictingShallowOptions),
+            (Some(depth), None) => builder.depth(depth),
+            (None, Some(cutoff)) => builder.shallow_since(cutoff),
+            (None, None) => builder,
         };
-
-        repo.object_cache_size_if_unset(4 * 1024 * 1024);
-        let remote_name = repo.remote_names().into_iter().next().map(Cow::into_owned);
-        Ok(Index {
-            repo,
-            remote_name,
-            branch_name: "master",
-            seen_ref_name: LAST_SEEN_REFNAME,
-        })
+        builder.build_with_options(progress, should_interrupt)
     }
 
     /// Return a new `Index` instance from the given `path`, which should contain a bare or non-bare
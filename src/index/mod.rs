@@ -3,6 +3,7 @@ use std::str;
 
 static INDEX_GIT_URL: &str = "https://github.com/rust-lang/crates.io-index";
 static LAST_SEEN_REFNAME: &str = "refs/heads/crates-index-diff_last-seen";
+static CURSOR_REFNAME_PREFIX: &str = "refs/crates-index-diff/cursors/";
 
 /// Declarative macro to generate `impl From<Src> for Error` where the source
 /// error value is boxed into the given `Error` enum variant.
@@ -30,12 +31,20 @@ macro_rules! impl_from_boxed {
 pub struct CloneOptions {
     /// The url to clone the crates-index repository from.
     pub url: String,
+    /// If set, only clone this amount of commits, with `1` meaning only the latest one, instead of the complete history.
+    ///
+    /// Setting it along with [`shallow_since`](Self::shallow_since) is an error.
+    pub depth: Option<std::num::NonZeroU32>,
+    /// If set, only clone commits made after this time, instead of the complete history.
+    pub shallow_since: Option<gix::date::Time>,
 }
 
 impl Default for CloneOptions {
     fn default() -> Self {
         CloneOptions {
             url: INDEX_GIT_URL.into(),
+            depth: None,
+            shallow_since: None,
         }
     }
 }
@@ -56,11 +65,88 @@ impl Index {
     pub fn last_seen_reference(
         &self,
     ) -> Result<gix::Reference<'_>, gix::reference::find::existing::Error> {
-        self.repo.find_reference(self.seen_ref_name)
+        self.repo.find_reference(self.seen_ref_name.as_str())
     }
+
+    /// Return the name and path of the reference used to keep track of the last seen state of the
+    /// crates.io repository, like `refs/heads/crates-index-diff_last-seen`.
+    pub fn seen_ref_name(&self) -> &str {
+        &self.seen_ref_name
+    }
+
+    /// Return the name of the branch to fetch, like `master`.
+    pub fn branch_name(&self) -> &str {
+        &self.branch_name
+    }
+
+    /// Return the default branch of the remote as detected when opening the index, which differs from
+    /// [`Self::branch_name()`] if another branch was configured, or `None` if it couldn't be detected.
+    pub fn default_branch_name(&self) -> Option<&str> {
+        self.default_branch_name.as_deref()
+    }
+
+    /// Return the amount of threads to use when diffing, as [configured](init::IndexBuilder::diff_threads()).
+    pub fn diff_threads(&self) -> usize {
+        self.diff_threads
+    }
+
+    /// Return what to do if a line can't be decoded, as [configured](init::IndexBuilder::decode_policy()).
+    pub fn decode_policy(&self) -> diff::DecodePolicy {
+        self.decode_policy
+    }
+
+    /// Return the crates to look at when diffing, as [configured](init::IndexBuilder::crate_filter()).
+    pub fn crate_filter(&self) -> &diff::CrateFilter {
+        &self.crate_filter
+    }
+
+    /// Return how to use the GitHub API, as [configured](init::IndexBuilder::github_fast_path()).
+    pub fn github_fast_path(&self) -> &diff::GithubFastPath {
+        &self.github_fast_path
+    }
+
+    /// Return where to start looking for changes if nothing was seen yet,
+    /// as [configured](init::IndexBuilder::start_from()).
+    pub fn start_from(&self) -> diff::StartFrom {
+        self.start_from
+    }
+
+    /// Return whether ordered changes fail if they can't be ordered due to a squash,
+    /// as [configured](init::IndexBuilder::fail_on_squash()).
+    pub fn fail_on_squash(&self) -> bool {
+        self.fail_on_squash
+    }
+}
+
+/// Return the path of the file holding all versions of the crate called `name`, relative to the root of the index.
+///
+/// This is the layout shared by the git index and the sparse index, i.e. `1/a`, `2/ab`, `3/a/abc` and `ab/cd/abcd…`.
+/// Return `None` if `name` can't be the name of a crate, i.e. if it's empty or contains anything but
+/// ASCII alphanumerics, `-` and `_`, just like crates.io requires.
+pub(crate) fn crate_name_to_relative_path(name: &str) -> Option<String> {
+    let is_valid = !name.is_empty()
+        && name
+            .bytes()
+            .all(|b| b.is_ascii_alphanumeric() || b == b'-' || b == b'_');
+    if !is_valid {
+        return None;
+    }
+    let name = name.to_ascii_lowercase();
+    Some(match name.len() {
+        1 => format!("1/{name}"),
+        2 => format!("2/{name}"),
+        3 => format!("3/{}/{name}", &name[..1]),
+        _ => format!("{}/{}/{name}", &name[..2], &name[2..4]),
+    })
 }
 
+/// Named positions in the index history for multiple independent consumers
+pub mod cursor;
 /// Main index diff functionality
 pub mod diff;
 /// initial index repo loading & cloning
 pub mod init;
+/// Point-in-time views of all crates in the index
+pub mod snapshot;
+/// Detect squashed history and find changes through it
+pub mod squash;
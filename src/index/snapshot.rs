@@ -0,0 +1,134 @@
+use crate::index::crate_name_to_relative_path;
+use crate::index::diff::delegate::version_from_json_line;
+use crate::index::diff::Error;
+use crate::{CrateVersion, Index};
+use bstr::{BStr, BString, ByteSlice};
+use std::sync::atomic::AtomicBool;
+
+/// A view of all crates in the crates index as they were at a specific revision.
+///
+/// Obtain one with [`Index::snapshot()`].
+pub struct Snapshot<'index> {
+    tree: gix::Tree<'index>,
+}
+
+/// The file of a single crate in a [`Snapshot`], holding all of its versions.
+///
+/// Versions are only parsed when they are accessed.
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct Crate {
+    name: BString,
+    data: Vec<u8>,
+}
+
+/// Snapshots
+impl Index {
+    /// Return a view of all crates in the index as they were at revision `rev`, like `HEAD~10` or a commit hash,
+    /// to learn which versions of a crate existed and if they were yanked at that time.
+    ///
+    /// An error is returned if `rev` is a range, like `HEAD~2..HEAD`.
+    pub fn snapshot(&self, rev: impl AsRef<str>) -> Result<Snapshot<'_>, Error> {
+        let tree = self
+            .repo
+            .rev_parse_single(rev.as_ref())?
+            .object()?
+            .peel_to_tree()?;
+        Ok(Snapshot { tree })
+    }
+
+    /// Fetch the remote, mark its latest state as seen and return a snapshot of it, reporting `progress`
+    /// and stopping once `should_interrupt` is set.
+    ///
+    /// This allows new consumers to start with the complete state of the index instead of processing
+    /// all changes since the beginning of history, and to learn about changes made from now on
+    /// with [`Self::fetch_changes()`].
+    pub fn fetch_snapshot_with_options<P>(
+        &self,
+        progress: P,
+        should_interrupt: &AtomicBool,
+    ) -> Result<Snapshot<'_>, Error>
+    where
+        P: gix::NestedProgress,
+        P::SubProgress: 'static,
+    {
+        let to = self.fetch_remote(progress, should_interrupt)?;
+        self.set_last_seen_reference(to)?;
+        let tree = self.repo.find_object(to)?.peel_to_tree()?;
+        Ok(Snapshot { tree })
+    }
+}
+
+impl<'index> Snapshot<'index> {
+    /// Return the id of the tree this snapshot is looking at.
+    pub fn tree_id(&self) -> gix::hash::ObjectId {
+        self.tree.id
+    }
+
+    /// Return the crate called `name`, or `None` if there is no such crate in this snapshot.
+    ///
+    /// Just like on crates.io, `name` is matched case-insensitively.
+    pub fn find_crate(&self, name: &str) -> Result<Option<Crate>, Error> {
+        let Some(relative_path) = crate_name_to_relative_path(name) else {
+            return Ok(None);
+        };
+        let Some(entry) = self.tree.lookup_entry_by_path(relative_path)? else {
+            return Ok(None);
+        };
+        if !entry.mode().is_blob() {
+            return Ok(None);
+        }
+        Ok(Some(Crate {
+            name: entry.filename().to_owned(),
+            data: entry.object()?.detach().data,
+        }))
+    }
+
+    /// Return an iterator over all crates in this snapshot, in no particular order.
+    ///
+    /// Only the location of all crate files is obtained upfront, their content is loaded one crate at a time.
+    pub fn crates(&self) -> Result<impl Iterator<Item = Result<Crate, Error>> + 'index, Error> {
+        let repo = self.tree.repo;
+        let files = self.tree.traverse().breadthfirst.files()?;
+        Ok(files
+            .into_iter()
+            .filter(|entry| entry.mode.is_blob() && !entry.filepath.contains(&b'.'))
+            .map(move |entry| {
+                let name = entry
+                    .filepath
+                    .rsplit_str("/")
+                    .next()
+                    .expect("split yields at least one item");
+                Ok(Crate {
+                    name: name.into(),
+                    data: repo.find_object(entry.oid)?.detach().data,
+                })
+            }))
+    }
+}
+
+impl Crate {
+    /// Return the name of the crate as stored in the index, which is always lower-case.
+    ///
+    /// Use the [`name`](CrateVersion::name) of its versions to learn how it's spelled.
+    pub fn name(&self) -> &BStr {
+        self.name.as_ref()
+    }
+
+    /// Return an iterator over all versions of this crate in the order they were published, parsing them one by one.
+    pub fn versions(&self) -> impl Iterator<Item = Result<CrateVersion, Error>> + '_ {
+        self.data
+            .lines()
+            .map(move |line| version_from_json_line(line, self.name.as_ref()))
+    }
+
+    /// Return the version of this crate whose version number is `version`, like `1.0.0`, or `None` if there is no such version.
+    pub fn version(&self, version: &str) -> Result<Option<CrateVersion>, Error> {
+        for candidate in self.versions() {
+            let candidate = candidate?;
+            if candidate.version == version {
+                return Ok(Some(candidate));
+            }
+        }
+        Ok(None)
+    }
+}
@@ -0,0 +1,236 @@
+use crate::index::diff::{Error, Order, Outcome};
+use crate::{Change, Index};
+use bstr::ByteSlice;
+use gix::prelude::ObjectIdExt;
+use std::sync::atomic::AtomicBool;
+
+/// The prefix of the name of branches crates.io publishes with the history of the index before it was squashed,
+/// like `snapshot-2024-01-01`.
+const SNAPSHOT_BRANCH_PREFIX: &str = "snapshot-";
+
+/// The history of the crates index was squashed, so a previously seen commit isn't an ancestor of the latest one.
+///
+/// Obtain it with [`Index::detect_squash()`].
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct SquashDetected {
+    /// The commit that was seen last, which is part of the old history.
+    pub last_seen: gix::hash::ObjectId,
+    /// The first commit of the old history which contains `last_seen`.
+    pub old_root: gix::hash::ObjectId,
+    /// The first commit of the new history, which typically contains the whole index as it was when squashing.
+    pub new_root: gix::hash::ObjectId,
+    /// The branch with the old history up to the squash, if one is available locally.
+    pub snapshot: Option<SnapshotBranch>,
+}
+
+/// A branch that crates.io publishes when squashing the index, with all history up to the squash.
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct SnapshotBranch {
+    /// The full name of the reference, like `refs/remotes/origin/snapshot-2024-01-01`.
+    pub name: gix::refs::FullName,
+    /// The commit the branch points to, which is the last commit before the squash.
+    pub tip: gix::hash::ObjectId,
+}
+
+/// Squashed history
+impl Index {
+    /// Return information about the squash that happened if `last_seen` isn't an ancestor of `current_commit`,
+    /// or `None` if the history is linear.
+    ///
+    /// If `last_seen` isn't a commit, like the empty tree used before anything was seen, there is no squash either.
+    /// The same is true if `current_commit` is an ancestor of `last_seen`, i.e. if it's older.
+    ///
+    /// To find the [snapshot branch](SquashDetected::snapshot), it must have been fetched already, for instance with
+    /// a refspec like `+refs/heads/snapshot-*:refs/remotes/origin/snapshot-*`.
+    pub fn detect_squash(
+        &self,
+        last_seen: impl Into<gix::hash::ObjectId>,
+        current_commit: impl Into<gix::hash::ObjectId>,
+    ) -> Result<Option<SquashDetected>, Error> {
+        let (last_seen, current_commit) = (last_seen.into(), current_commit.into());
+        let is_commit = last_seen.attach(&self.repo).object()?.kind.is_commit();
+        if !is_commit {
+            return Ok(None);
+        }
+        match self.merge_base(last_seen, current_commit)? {
+            Some(base) if base == last_seen || base == current_commit => return Ok(None),
+            _ => {}
+        }
+        Ok(Some(SquashDetected {
+            last_seen,
+            old_root: self.root_commit(last_seen)?,
+            new_root: self.root_commit(current_commit)?,
+            snapshot: self.find_snapshot_branch(last_seen)?,
+        }))
+    }
+
+    /// Return the first `snapshot-*` branch, sorted by name, which contains `commit` in its history.
+    pub fn find_snapshot_branch(
+        &self,
+        commit: impl Into<gix::hash::ObjectId>,
+    ) -> Result<Option<SnapshotBranch>, Error> {
+        let commit = commit.into();
+        let mut candidates = Vec::new();
+        for reference in self
+            .repo
+            .references()
+            .map_err(|err| Error::ListReferences(err.into()))?
+            .all()
+            .map_err(|err| Error::ListReferences(err.into()))?
+        {
+            let mut reference = reference.map_err(Error::ListReferences)?;
+            let is_snapshot = reference
+                .name()
+                .as_bstr()
+                .rsplit_str("/")
+                .next()
+                .is_some_and(|name| name.starts_with(SNAPSHOT_BRANCH_PREFIX.as_bytes()));
+            if !is_snapshot {
+                continue;
+            }
+            let Ok(tip) = reference.peel_to_id() else {
+                continue;
+            };
+            candidates.push(SnapshotBranch {
+                name: reference.name().to_owned(),
+                tip: tip.detach(),
+            });
+        }
+        candidates.sort_by(|a, b| a.name.cmp(&b.name));
+        for branch in candidates {
+            if self.merge_base(commit, branch.tip)? == Some(commit) {
+                return Ok(Some(branch));
+            }
+        }
+        Ok(None)
+    }
+
+    /// Return all changes from `squash.last_seen` to `current_commit`, which must be part of the history
+    /// after the squash.
+    ///
+    /// If the [snapshot branch](SquashDetected::snapshot) is known, changes are ordered
+    /// [as in the crates index](Order::AsInCratesIndex) up to the tip of the snapshot branch and after the new root,
+    /// with the changes made by squashing in between.
+    /// Otherwise, this is the same as diffing both commits directly, with the returned [`Order`] being
+    /// [implementation defined](Order::ImplementationDefined).
+    pub fn changes_through_squash(
+        &self,
+        squash: &SquashDetected,
+        current_commit: impl Into<gix::hash::ObjectId>,
+    ) -> Result<(Vec<Change>, Order), Error> {
+        self.changes_through_squash_with_options(
+            squash,
+            current_commit,
+            gix::progress::Discard,
+            &AtomicBool::default(),
+        )
+    }
+
+    /// Like [`Self::changes_through_squash()`], but reports the progress of each diff as `progress`
+    /// and stops with [`Error::Interrupted`] once `should_interrupt` is set.
+    pub fn changes_through_squash_with_options(
+        &self,
+        squash: &SquashDetected,
+        current_commit: impl Into<gix::hash::ObjectId>,
+        progress: impl gix::Progress,
+        should_interrupt: &AtomicBool,
+    ) -> Result<(Vec<Change>, Order), Error> {
+        let outcome = self.changes_through_squash_inner(
+            squash,
+            current_commit.into(),
+            progress,
+            should_interrupt,
+        )?;
+        Ok((outcome.changes, outcome.order))
+    }
+
+    pub(crate) fn changes_through_squash_inner(
+        &self,
+        squash: &SquashDetected,
+        current_commit: gix::hash::ObjectId,
+        mut progress: impl gix::Progress,
+        should_interrupt: &AtomicBool,
+    ) -> Result<Outcome, Error> {
+        let Some(snapshot) = &squash.snapshot else {
+            return self.changes_between_commits_inner(
+                squash.last_seen,
+                current_commit,
+                progress,
+                should_interrupt,
+            );
+        };
+        let mut outcome = self.changes_between_ancestor_commits_inner(
+            squash.last_seen,
+            snapshot.tip,
+            &mut progress,
+            should_interrupt,
+        )?;
+        let squashed = self.changes_between_commits_inner(
+            snapshot.tip,
+            squash.new_root,
+            &mut progress,
+            should_interrupt,
+        )?;
+        let after = self.changes_between_ancestor_commits_inner(
+            squash.new_root,
+            current_commit,
+            &mut progress,
+            should_interrupt,
+        )?;
+        if after.order != Order::AsInCratesIndex {
+            outcome.order = Order::ImplementationDefined;
+        }
+        for Outcome {
+            changes,
+            diagnostics,
+            ..
+        } in [squashed, after]
+        {
+            outcome.changes.extend(changes);
+            outcome.diagnostics.extend(diagnostics);
+        }
+        Ok(outcome)
+    }
+
+    /// Return the best common ancestor of `one` and `two`, or `None` if their histories aren't connected.
+    ///
+    /// Unlike [`Self::commit_ancestry()`], this follows all parents and doesn't rely on commit times.
+    fn merge_base(
+        &self,
+        one: gix::hash::ObjectId,
+        two: gix::hash::ObjectId,
+    ) -> Result<Option<gix::hash::ObjectId>, Error> {
+        match self.repo.merge_base(one, two) {
+            Ok(base) => Ok(Some(base.detach())),
+            Err(gix::repository::merge_base::Error::NotFound { .. }) => Ok(None),
+            Err(err) => Err(err.into()),
+        }
+    }
+
+    /// Return the first commit reachable from `commit` by following first parents.
+    ///
+    /// In shallow clones, this is the first commit at the shallow boundary as its parents aren't available.
+    fn root_commit(&self, commit: gix::hash::ObjectId) -> Result<gix::hash::ObjectId, Error> {
+        let shallow_commits = self.repo.shallow_commits().ok().flatten();
+        let mut root = commit;
+        loop {
+            if shallow_commits
+                .as_ref()
+                .is_some_and(|commits| commits.contains(&root))
+            {
+                return Ok(root);
+            }
+            let parent = root
+                .attach(&self.repo)
+                .object()?
+                .try_into_commit()?
+                .parent_ids()
+                .next()
+                .map(|id| id.detach());
+            match parent {
+                Some(parent) => root = parent,
+                None => return Ok(root),
+            }
+        }
+    }
+}
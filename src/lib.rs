@@ -2,12 +2,21 @@
 //!
 //! Have a look at the real-world usage to learn more about it:
 //! [crates-io-cli](https://github.com/Byron/crates-io-cli-rs/blob/b7a39ad8ef68adb81b2d8a7e552cb0a2a73f7d5b/src/main.rs#L62)
+//!
+//! ## The `async` feature
+//!
+//! It provides `Index::peek_changes_async()` and `Index::fetch_changes_async()` for use within a `tokio` runtime.
+//! Only the GitHub fast-path is truly async though, as fetching and diffing are done with blocking IO
+//! inside of `tokio::task::spawn_blocking()`.
 #![deny(missing_docs, rust_2018_idioms, unsafe_code)]
 
+mod compact;
 /// Access to the main `Index` type and related functionality.
 pub mod index;
+/// Learn about changes in a sparse HTTP index, like `https://index.crates.io`.
+pub mod sparse;
 mod types;
 /// Access to all `gitoxide` functionality.
 pub use gix;
 
-pub use types::{Change, CrateVersion, Dependency, DependencyKind, Index};
+pub use types::{Change, CrateVersion, Dependency, DependencyKind, Index, RegistryConfig};
@@ -0,0 +1,148 @@
+//! Inspect the changes made to a clone of the crates index from the command-line.
+#![deny(rust_2018_idioms, unsafe_code)]
+
+use clap::{Parser, Subcommand, ValueEnum};
+use crates_index_diff::index::diff::Order;
+use crates_index_diff::{gix, Change, Index};
+use std::io::Write;
+use std::path::PathBuf;
+use std::sync::atomic::AtomicBool;
+use std::sync::Arc;
+
+type Result<T = ()> = std::result::Result<T, Box<dyn std::error::Error + Send + Sync + 'static>>;
+
+/// Learn what's changed in the crates index.
+#[derive(Parser)]
+#[clap(name = "crates-index-diff", version)]
+struct Args {
+    /// The directory containing the clone of the crates index, which is cloned if it doesn't exist yet.
+    #[clap(long, short = 'i', default_value = "crates.io-index")]
+    index: PathBuf,
+    /// The URL to clone the crates index from, if it doesn't exist yet.
+    #[clap(long)]
+    url: Option<String>,
+    /// The name of the branch to fetch, if not the default branch of the remote.
+    #[clap(long)]
+    branch: Option<String>,
+    /// The way changes are printed.
+    #[clap(long, short = 'f', value_enum, default_value_t = Format::Text)]
+    format: Format,
+    /// Provide changes in the order they were made in the crates index, commit by commit.
+    ///
+    /// This is slower, but allows to see exactly what happened when.
+    #[clap(long, global = true)]
+    ordered: bool,
+    #[clap(subcommand)]
+    cmd: Subcommands,
+}
+
+#[derive(Subcommand)]
+enum Subcommands {
+    /// Fetch the remote and print all changes since the last seen commit, then mark them as seen.
+    Fetch,
+    /// Fetch the remote and print all changes since the last seen commit, without marking them as seen.
+    Peek,
+    /// Print all changes between two revisions, like `HEAD~10` and `HEAD`, without fetching.
+    Between {
+        /// The revision to start from.
+        from: String,
+        /// The revision to end at.
+        to: String,
+    },
+}
+
+#[derive(Copy, Clone, ValueEnum)]
+enum Format {
+    /// One line per change, for humans.
+    Text,
+    /// One JSON object per change and line, for programs.
+    Json,
+}
+
+fn main() -> Result {
+    let args = Args::parse();
+    let should_interrupt = Arc::new(AtomicBool::default());
+    for signal in [signal_hook::consts::SIGINT, signal_hook::consts::SIGTERM] {
+        // The first signal interrupts gracefully, the second one terminates right away.
+        signal_hook::flag::register_conditional_shutdown(signal, 1, should_interrupt.clone())?;
+        signal_hook::flag::register(signal, should_interrupt.clone())?;
+    }
+    let mut builder = Index::builder(&args.index);
+    if let Some(url) = args.url {
+        builder = builder.url(url);
+    }
+    if let Some(branch) = args.branch {
+        builder = builder.branch_name(branch);
+    }
+    let index = builder.build_with_options(gix::progress::Discard, &should_interrupt)?;
+    let order = if args.ordered {
+        Order::AsInCratesIndex
+    } else {
+        Order::ImplementationDefined
+    };
+
+    let changes = match args.cmd {
+        Subcommands::Fetch => {
+            index.fetch_changes_with_options(gix::progress::Discard, &should_interrupt, order)?
+        }
+        Subcommands::Peek => {
+            let (changes, to) = index.peek_changes_with_options(
+                gix::progress::Discard,
+                &should_interrupt,
+                order,
+            )?;
+            eprintln!("changes up to {to}");
+            changes
+        }
+        Subcommands::Between { from, to } => {
+            let repo = index.repository();
+            let from = repo.rev_parse_single(from.as_str())?.detach();
+            let to = repo.rev_parse_single(to.as_str())?.detach();
+            if args.ordered {
+                let (changes, order) = index.changes_between_ancestor_commits_with_options(
+                    from,
+                    to,
+                    gix::progress::Discard,
+                    &should_interrupt,
+                )?;
+                if order != Order::AsInCratesIndex {
+                    eprintln!("'from' isn't an ancestor of 'to', changes are not ordered");
+                }
+                changes
+            } else {
+                index.changes_between_commits_with_options(
+                    from,
+                    to,
+                    gix::progress::Discard,
+                    &should_interrupt,
+                )?
+            }
+        }
+    };
+
+    let mut out = std::io::stdout().lock();
+    for change in &changes {
+        match args.format {
+            Format::Text => writeln!(out, "{}", to_text(change))?,
+            Format::Json => writeln!(out, "{}", serde_json::to_string(change)?)?,
+        }
+    }
+    Ok(())
+}
+
+fn to_text(change: &Change) -> String {
+    let versions = change
+        .versions()
+        .iter()
+        .map(|v| v.version.as_str())
+        .collect::<Vec<_>>()
+        .join(", ");
+    match change {
+        Change::CrateDeleted { name, .. } => format!("{change} {name} ({versions})"),
+        Change::ConfigChanged { new, .. } => match new {
+            Some(config) => format!("{change} dl={}", config.dl),
+            None => format!("{change} (deleted)"),
+        },
+        _ => format!("{change} {}@{versions}", change.versions()[0].name),
+    }
+}
@@ -0,0 +1,322 @@
+use crate::index::crate_name_to_relative_path;
+use crate::index::diff::delegate::Delegate;
+use crate::Change;
+use reqwest::header::{ETAG, IF_MODIFIED_SINCE, IF_NONE_MATCH, LAST_MODIFIED};
+use reqwest::StatusCode;
+use std::path::{Path, PathBuf};
+
+static SPARSE_INDEX_URL: &str = "https://index.crates.io";
+static CONFIG_FILE_NAME: &str = "config.json";
+
+/// The error returned by methods of the [`SparseIndex`].
+#[derive(Debug, thiserror::Error)]
+#[allow(missing_docs)]
+pub enum Error {
+    #[error("Failed to request {url:?} from the sparse index")]
+    Request { source: reqwest::Error, url: String },
+    #[error("The sparse index responded with status {status} for {url:?}")]
+    UnexpectedStatus { status: StatusCode, url: String },
+    #[error("{name:?} is not a valid crate name")]
+    InvalidCrateName { name: String },
+    #[error("The sparse index at {url:?} has no config.json and is likely not a sparse index")]
+    MissingConfig { url: String },
+    #[error("Could not read or write the state of {path:?}")]
+    Io {
+        source: std::io::Error,
+        path: PathBuf,
+    },
+    #[error("Could not decode or encode the state of {path:?}")]
+    StateDecode {
+        source: serde_json::Error,
+        path: PathBuf,
+    },
+    #[error(transparent)]
+    Diff(#[from] crate::index::diff::Error),
+}
+
+/// A sparse HTTP index, like the one at `https://index.crates.io`, which is polled file by file.
+///
+/// Unlike the git index, a sparse index can't be listed, so the crates to watch must be named explicitly.
+/// For each of these crates, the last seen file content along with its `ETag` and `Last-Modified` headers are
+/// kept in [`state_dir`](Self::state_dir()), which allows to only download files that actually changed and to
+/// diff them line by line just like the git index does.
+pub struct SparseIndex {
+    url: String,
+    state_dir: PathBuf,
+    client: reqwest::blocking::Client,
+}
+
+/// The state of all files that were polled by [`SparseIndex::peek_changes()`], to be passed to
+/// [`SparseIndex::set_last_seen()`] to mark them as seen.
+#[derive(Default, Debug, Clone)]
+pub struct LastSeen {
+    files: Vec<SeenFile>,
+}
+
+#[derive(Debug, Clone)]
+struct SeenFile {
+    relative_path: String,
+    /// The new file content along with its validators, or `None` if the file was deleted.
+    state: Option<(Vec<u8>, Validators)>,
+}
+
+/// The HTTP headers used to only obtain a file if it changed since we last saw it.
+#[derive(Default, Debug, Clone, serde::Serialize, serde::Deserialize)]
+struct Validators {
+    etag: Option<String>,
+    last_modified: Option<String>,
+}
+
+enum Response {
+    NotModified,
+    NotFound,
+    Modified {
+        data: Vec<u8>,
+        validators: Validators,
+    },
+}
+
+/// Initialization
+impl SparseIndex {
+    /// Return a new instance for polling the crates.io sparse index, storing the last seen state in `state_dir`.
+    pub fn from_state_dir(state_dir: impl Into<PathBuf>) -> Result<Self, Error> {
+        Self::with_url(SPARSE_INDEX_URL, state_dir)
+    }
+
+    /// Return a new instance for polling the sparse index at `url`, like `https://index.crates.io`,
+    /// storing the last seen state in `state_dir`.
+    pub fn with_url(url: impl Into<String>, state_dir: impl Into<PathBuf>) -> Result<Self, Error> {
+        let url = url.into();
+        let client = reqwest::blocking::Client::builder()
+            .user_agent("crates-index-diff")
+            .build()
+            .map_err(|source| Error::Request {
+                source,
+                url: url.clone(),
+            })?;
+        Ok(SparseIndex {
+            url: url.trim_end_matches('/').to_owned(),
+            state_dir: state_dir.into(),
+            client,
+        })
+    }
+}
+
+/// Access
+impl SparseIndex {
+    /// Return the base URL of the sparse index, without trailing slash.
+    pub fn url(&self) -> &str {
+        &self.url
+    }
+
+    /// Return the directory in which the last seen state of all polled files is kept.
+    pub fn state_dir(&self) -> &Path {
+        &self.state_dir
+    }
+}
+
+/// Find changes
+impl SparseIndex {
+    /// Return all [`Change`]s of the crates named `crate_names` that are observed between the last time
+    /// [`Self::set_last_seen()`] was called and the current state of the sparse index, along with the state
+    /// to pass to [`Self::set_last_seen()`] to mark these changes as seen.
+    ///
+    /// Crates that are polled for the first time will have all of their versions reported as added.
+    /// Changes to the registry configuration are reported before all others, just like the git index does.
+    /// The order of changes within one crate follows the one of the lines in the crate file, and crates
+    /// are reported in the order in which they are named.
+    ///
+    /// Names must be valid crate names, i.e. consist of ASCII alphanumerics, `-` and `_` only, or
+    /// [`Error::InvalidCrateName`] is returned.
+    /// Just like on crates.io, they are matched case-insensitively.
+    pub fn peek_changes<'a>(
+        &self,
+        crate_names: impl IntoIterator<Item = &'a str>,
+    ) -> Result<(Vec<Change>, LastSeen), Error> {
+        let mut seen = LastSeen::default();
+        let mut delegate = Delegate::default();
+        match self.poll(CONFIG_FILE_NAME)? {
+            Response::NotFound => {
+                return Err(Error::MissingConfig {
+                    url: self.url.clone(),
+                })
+            }
+            Response::NotModified => {}
+            Response::Modified { data, validators } => {
+                delegate
+                    .config_changed(self.read_state(CONFIG_FILE_NAME)?.as_deref(), Some(&data))?;
+                seen.files.push(SeenFile {
+                    relative_path: CONFIG_FILE_NAME.into(),
+                    state: Some((data, validators)),
+                });
+            }
+        }
+
+        for name in crate_names {
+            let relative_path =
+                crate_name_to_relative_path(name).ok_or_else(|| Error::InvalidCrateName {
+                    name: name.to_owned(),
+                })?;
+            // The name of the crate file, which is what the git index reports as well.
+            let name = name.to_ascii_lowercase();
+            let name = name.as_str();
+            let state = match self.poll(&relative_path)? {
+                Response::NotModified => continue,
+                Response::NotFound => {
+                    match self.read_state(&relative_path)? {
+                        Some(old) => delegate.deleted(name.into(), &old)?,
+                        None => continue,
+                    }
+                    None
+                }
+                Response::Modified { data, validators } => {
+                    match self.read_state(&relative_path)? {
+                        Some(old) => delegate.modified(name.into(), &old, &data)?,
+                        None => delegate.added(name.into(), &data)?,
+                    }
+                    Some((data, validators))
+                }
+            };
+            seen.files.push(SeenFile {
+                relative_path,
+                state,
+            });
+        }
+        Ok((delegate.into_result()?, seen))
+    }
+
+    /// As [`Self::peek_changes()`], but marks the returned changes as seen right away so the next call
+    /// only reports changes that happened in the meantime.
+    pub fn fetch_changes<'a>(
+        &self,
+        crate_names: impl IntoIterator<Item = &'a str>,
+    ) -> Result<Vec<Change>, Error> {
+        let (changes, seen) = self.peek_changes(crate_names)?;
+        self.set_last_seen(seen)?;
+        Ok(changes)
+    }
+
+    /// Persist the state of all files polled by [`Self::peek_changes()`] so their changes aren't reported again.
+    pub fn set_last_seen(&self, seen: LastSeen) -> Result<(), Error> {
+        for SeenFile {
+            relative_path,
+            state,
+        } in seen.files
+        {
+            let data_path = self.state_dir.join(&relative_path);
+            let validators_path = validators_path(&data_path);
+            match state {
+                Some((data, validators)) => {
+                    if let Some(dir) = data_path.parent() {
+                        std::fs::create_dir_all(dir).map_err(|source| Error::Io {
+                            source,
+                            path: dir.to_owned(),
+                        })?;
+                    }
+                    std::fs::write(&data_path, data).map_err(|source| Error::Io {
+                        source,
+                        path: data_path.clone(),
+                    })?;
+                    let validators =
+                        serde_json::to_vec(&validators).map_err(|source| Error::StateDecode {
+                            source,
+                            path: validators_path.clone(),
+                        })?;
+                    std::fs::write(&validators_path, validators).map_err(|source| Error::Io {
+                        source,
+                        path: validators_path,
+                    })?;
+                }
+                None => {
+                    for path in [data_path, validators_path] {
+                        match std::fs::remove_file(&path) {
+                            Err(err) if err.kind() != std::io::ErrorKind::NotFound => {
+                                return Err(Error::Io { source: err, path })
+                            }
+                            _ => {}
+                        }
+                    }
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Utilities
+impl SparseIndex {
+    /// Request the file at `relative_path`, conditional on it having changed since we last saw it.
+    fn poll(&self, relative_path: &str) -> Result<Response, Error> {
+        let url = format!("{}/{}", self.url, relative_path);
+        let validators = self.read_validators(relative_path)?;
+        let mut request = self.client.get(&url);
+        if let Some(etag) = &validators.etag {
+            request = request.header(IF_NONE_MATCH, etag);
+        }
+        if let Some(last_modified) = &validators.last_modified {
+            request = request.header(IF_MODIFIED_SINCE, last_modified);
+        }
+        let response = request.send().map_err(|source| Error::Request {
+            source,
+            url: url.clone(),
+        })?;
+
+        let status = response.status();
+        if status == StatusCode::NOT_MODIFIED {
+            Ok(Response::NotModified)
+        } else if status == StatusCode::NOT_FOUND
+            || status == StatusCode::GONE
+            || status == StatusCode::UNAVAILABLE_FOR_LEGAL_REASONS
+        {
+            // These are the status codes cargo considers to mean that a crate doesn't exist.
+            Ok(Response::NotFound)
+        } else if status.is_success() {
+            let header = |name| {
+                response
+                    .headers()
+                    .get(name)
+                    .and_then(|v| v.to_str().ok())
+                    .map(ToOwned::to_owned)
+            };
+            let validators = Validators {
+                etag: header(ETAG),
+                last_modified: header(LAST_MODIFIED),
+            };
+            let data = response
+                .bytes()
+                .map_err(|source| Error::Request { source, url })?;
+            Ok(Response::Modified {
+                data: data.into(),
+                validators,
+            })
+        } else {
+            Err(Error::UnexpectedStatus { status, url })
+        }
+    }
+
+    fn read_state(&self, relative_path: &str) -> Result<Option<Vec<u8>>, Error> {
+        let path = self.state_dir.join(relative_path);
+        match std::fs::read(&path) {
+            Ok(data) => Ok(Some(data)),
+            Err(err) if err.kind() == std::io::ErrorKind::NotFound => Ok(None),
+            Err(source) => Err(Error::Io { source, path }),
+        }
+    }
+
+    fn read_validators(&self, relative_path: &str) -> Result<Validators, Error> {
+        let path = validators_path(&self.state_dir.join(relative_path));
+        match std::fs::read(&path) {
+            Ok(data) => {
+                serde_json::from_slice(&data).map_err(|source| Error::StateDecode { source, path })
+            }
+            Err(err) if err.kind() == std::io::ErrorKind::NotFound => Ok(Validators::default()),
+            Err(source) => Err(Error::Io { source, path }),
+        }
+    }
+}
+
+fn validators_path(data_path: &Path) -> PathBuf {
+    let mut path = data_path.as_os_str().to_owned();
+    path.push(".validators");
+    path.into()
+}
@@ -6,21 +6,46 @@ use std::hash::Hash;
 use std::{fmt, slice};
 
 /// A wrapper for a repository of the crates.io index.
+///
+/// Use [`Index::builder()`] to configure how it's opened or cloned.
+#[derive(Clone)]
 pub struct Index {
     /// The name and path of the reference used to keep track of the last seen state of the
     /// crates.io repository. The default value is `refs/heads/crates-index-diff_last-seen`.
-    pub seen_ref_name: &'static str,
+    pub(crate) seen_ref_name: String,
     /// The name of the branch to fetch. This value also affects the tracking branch.
-    pub branch_name: &'static str,
+    pub(crate) branch_name: String,
+    /// The default branch of the remote, as detected when opening the index.
+    pub(crate) default_branch_name: Option<String>,
     /// The name of the symbolic name of the remote to fetch from.
     /// If `None`, obtain the remote name from the configuration of the currently checked-out branch.
     pub remote_name: Option<BString>,
+    /// The amount of threads to use when diffing trees, with `0` meaning one thread per logical core.
+    pub(crate) diff_threads: usize,
+    /// What to do if a line in a crate file can't be decoded.
+    pub(crate) decode_policy: crate::index::diff::DecodePolicy,
+    /// The crates to look at when diffing.
+    pub(crate) crate_filter: crate::index::diff::CrateFilter,
+    /// How to use the GitHub API to avoid fetching if nothing changed.
+    pub(crate) github_fast_path: crate::index::diff::GithubFastPath,
+    /// Where to start looking for changes if nothing was seen yet.
+    pub(crate) start_from: crate::index::diff::StartFrom,
+    /// Whether ordered changes fail if they can't be ordered due to a squash.
+    pub(crate) fail_on_squash: bool,
     /// The git repository to use for diffing
     pub(crate) repo: gix::Repository,
 }
 
 /// Identify a kind of change that occurred to a crate
-#[derive(Clone, Eq, PartialEq, Debug)]
+///
+/// # Serialization
+///
+/// Changes are (de)serialized as objects tagged with their `kind` in `snake_case`, with all other
+/// fields named after the fields of the respective variant, or `version` for variants with a single
+/// crate version, like `{"kind":"yanked","version":{…}}` or `{"kind":"crate_deleted","name":"…","versions":[…]}`.
+/// This representation is stable and can be used to persist changes.
+#[derive(Clone, Eq, PartialEq, Debug, serde::Deserialize)]
+#[serde(from = "ChangeRepr")]
 pub enum Change {
     /// A crate version was added.
     Added(CrateVersion),
@@ -45,6 +70,26 @@ pub enum Change {
     /// Note that this is equivalent to deleting a line from a crates version file.
     /// Should more than one lines be removed per commit, the order of these changes is nondeterministic.
     VersionDeleted(CrateVersion),
+    /// The line of a crate version was rewritten in place, without changing its checksum or yanked state.
+    ///
+    /// This happens if crates.io adds fields like `rust_version` to existing versions, or fixes their dependencies.
+    /// Changes that only affect the representation of the line, but not its data, are not reported.
+    Modified {
+        /// The crate version as it was before the modification.
+        old: Box<CrateVersion>,
+        /// The crate version as it is after the modification.
+        new: Box<CrateVersion>,
+    },
+    /// The `config.json` file at the root of the index was added, changed or deleted, which affects where crates
+    /// are downloaded from and how the registry is accessed.
+    ///
+    /// It's reported for all crates, independently of the [crate filter](crate::index::init::IndexBuilder::crate_filter()).
+    ConfigChanged {
+        /// The configuration before the change, or `None` if the file was added.
+        old: Option<RegistryConfig>,
+        /// The configuration after the change, or `None` if the file was deleted.
+        new: Option<RegistryConfig>,
+    },
 }
 
 impl Change {
@@ -88,11 +133,29 @@ impl Change {
         }
     }
 
+    /// Return the crate version before and after its modification, if this is this kind of change.
+    pub fn modified(&self) -> Option<(&CrateVersion, &CrateVersion)> {
+        match self {
+            Change::Modified { old, new } => Some((old.as_ref(), new.as_ref())),
+            _ => None,
+        }
+    }
+
+    /// Return the registry configuration before and after the change, if this is this kind of change.
+    pub fn config_changed(&self) -> Option<(Option<&RegistryConfig>, Option<&RegistryConfig>)> {
+        match self {
+            Change::ConfigChanged { old, new } => Some((old.as_ref(), new.as_ref())),
+            _ => None,
+        }
+    }
+
     /// Returns all versions affected by this change.
     ///
     /// The returned slice usually has length 1.
     /// However, if a crate was purged from the index by an admin,
     /// all versions of the purged crate are returned.
+    /// For modifications, the version after the modification is returned.
+    /// Changes to the registry configuration don't affect any version, and return an empty slice.
     pub fn versions(&self) -> &[CrateVersion] {
         match self {
             Change::Added(v)
@@ -100,7 +163,9 @@ impl Change {
             | Change::AddedAndYanked(v)
             | Change::Yanked(v)
             | Change::VersionDeleted(v) => slice::from_ref(v),
+            Change::Modified { new, .. } => slice::from_ref(new),
             Change::CrateDeleted { versions, .. } => versions,
+            Change::ConfigChanged { .. } => &[],
         }
     }
 }
@@ -117,11 +182,118 @@ impl fmt::Display for Change {
                 Change::VersionDeleted(_) => "version deleted",
                 Change::Unyanked(_) => "unyanked",
                 Change::AddedAndYanked(_) => "added and yanked",
+                Change::Modified { .. } => "modified",
+                Change::ConfigChanged { .. } => "config changed",
             }
         )
     }
 }
 
+impl serde::Serialize for Change {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        ChangeRef::from(self).serialize(serializer)
+    }
+}
+
+/// The serialized form of a [`Change`], borrowing its data.
+#[derive(serde::Serialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+enum ChangeRef<'a> {
+    Added {
+        version: &'a CrateVersion,
+    },
+    Unyanked {
+        version: &'a CrateVersion,
+    },
+    AddedAndYanked {
+        version: &'a CrateVersion,
+    },
+    Yanked {
+        version: &'a CrateVersion,
+    },
+    CrateDeleted {
+        name: &'a str,
+        versions: &'a [CrateVersion],
+    },
+    VersionDeleted {
+        version: &'a CrateVersion,
+    },
+    Modified {
+        old: &'a CrateVersion,
+        new: &'a CrateVersion,
+    },
+    ConfigChanged {
+        old: Option<&'a RegistryConfig>,
+        new: Option<&'a RegistryConfig>,
+    },
+}
+
+impl<'a> From<&'a Change> for ChangeRef<'a> {
+    fn from(change: &'a Change) -> Self {
+        match change {
+            Change::Added(version) => ChangeRef::Added { version },
+            Change::Unyanked(version) => ChangeRef::Unyanked { version },
+            Change::AddedAndYanked(version) => ChangeRef::AddedAndYanked { version },
+            Change::Yanked(version) => ChangeRef::Yanked { version },
+            Change::CrateDeleted { name, versions } => ChangeRef::CrateDeleted { name, versions },
+            Change::VersionDeleted(version) => ChangeRef::VersionDeleted { version },
+            Change::Modified { old, new } => ChangeRef::Modified { old, new },
+            Change::ConfigChanged { old, new } => ChangeRef::ConfigChanged {
+                old: old.as_ref(),
+                new: new.as_ref(),
+            },
+        }
+    }
+}
+
+/// The deserialized form of a [`Change`], which must match [`ChangeRef`].
+#[derive(serde::Deserialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+enum ChangeRepr {
+    Added {
+        version: CrateVersion,
+    },
+    Unyanked {
+        version: CrateVersion,
+    },
+    AddedAndYanked {
+        version: CrateVersion,
+    },
+    Yanked {
+        version: CrateVersion,
+    },
+    CrateDeleted {
+        name: String,
+        versions: Vec<CrateVersion>,
+    },
+    VersionDeleted {
+        version: CrateVersion,
+    },
+    Modified {
+        old: Box<CrateVersion>,
+        new: Box<CrateVersion>,
+    },
+    ConfigChanged {
+        old: Option<RegistryConfig>,
+        new: Option<RegistryConfig>,
+    },
+}
+
+impl From<ChangeRepr> for Change {
+    fn from(change: ChangeRepr) -> Self {
+        match change {
+            ChangeRepr::Added { version } => Change::Added(version),
+            ChangeRepr::Unyanked { version } => Change::Unyanked(version),
+            ChangeRepr::AddedAndYanked { version } => Change::AddedAndYanked(version),
+            ChangeRepr::Yanked { version } => Change::Yanked(version),
+            ChangeRepr::CrateDeleted { name, versions } => Change::CrateDeleted { name, versions },
+            ChangeRepr::VersionDeleted { version } => Change::VersionDeleted(version),
+            ChangeRepr::Modified { old, new } => Change::Modified { old, new },
+            ChangeRepr::ConfigChanged { old, new } => Change::ConfigChanged { old, new },
+        }
+    }
+}
+
 /// Section in which a dependency was defined in.
 #[derive(
     Debug, Copy, Clone, serde::Serialize, serde::Deserialize, Eq, PartialEq, Hash, Ord, PartialOrd,
@@ -136,6 +308,23 @@ pub enum DependencyKind {
     Build,
 }
 
+/// The configuration of a registry, as stored in the `config.json` file at the root of its index.
+#[derive(Default, Clone, serde::Serialize, serde::Deserialize, Eq, PartialEq, Debug)]
+pub struct RegistryConfig {
+    /// The URL to download crates from, possibly with markers like `{crate}` and `{version}`.
+    pub dl: String,
+    /// The URL of the web API of the registry, like `https://crates.io`, if it has one.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub api: Option<String>,
+    /// If `true`, all requests to the registry, including downloads, must be authenticated.
+    #[serde(
+        rename = "auth-required",
+        default,
+        skip_serializing_if = "std::ops::Not::not"
+    )]
+    pub auth_required: bool,
+}
+
 /// Pack all information we know about a change made to a version of a crate.
 #[derive(Default, Clone, serde::Serialize, serde::Deserialize, Eq, PartialEq, Debug)]
 pub struct CrateVersion {
@@ -151,12 +340,48 @@ pub struct CrateVersion {
     pub checksum: [u8; 32],
     /// All cargo features
     pub features: HashMap<String, Vec<String>>,
+    /// Cargo features using the newer syntax, like `dep:name` or `name?/feature`, which older cargo versions
+    /// can't parse and thus are kept separate from [`features`](Self::features).
+    ///
+    /// Use [`all_features()`](Self::all_features()) to obtain all features at once.
+    #[serde(default, skip_serializing_if = "HashMap::is_empty")]
+    pub features2: HashMap<String, Vec<String>>,
     /// All crate dependencies
     #[serde(rename = "deps")]
     pub dependencies: Vec<Dependency>,
+    /// The version of the schema of this line, which is `2` if [`features2`](Self::features2) is used,
+    /// and unset for the original schema.
+    #[serde(rename = "v", skip_serializing_if = "Option::is_none")]
+    pub schema_version: Option<u32>,
+    /// The value of the `links` field in the manifest, i.e. the name of the native library this crate links to.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub links: Option<SmolString>,
+    /// The minimal supported rust version, like `1.70`, as specified in the manifest.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub rust_version: Option<SmolString>,
+    /// The time at which this version was published as RFC 3339 timestamp, like `2024-01-01T00:00:00Z`.
+    ///
+    /// It's only available for versions that were published after the field was introduced.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub pubtime: Option<SmolString>,
 }
 
 impl CrateVersion {
+    /// Return all cargo features, i.e. [`features`](Self::features) merged with [`features2`](Self::features2),
+    /// just like cargo sees them.
+    ///
+    /// Features defined in both maps are combined.
+    pub fn all_features(&self) -> HashMap<String, Vec<String>> {
+        let mut features = self.features.clone();
+        for (name, values) in &self.features2 {
+            features
+                .entry(name.clone())
+                .or_default()
+                .extend(values.iter().cloned());
+        }
+        features
+    }
+
     /// Parse and return this crate's version as a `semver::Version`.
     ///
     /// The crate index guarantees versions follow Semantic Versioning, so
@@ -164,12 +389,30 @@ impl CrateVersion {
     /// of that, and also rely on it.
     ///
     /// If parsing fails, this indicates a violation of that guarantee and
-    /// the method will panic.
+    /// the method will panic. Use [`try_version()`](Self::try_version()) to handle this case.
     #[cfg(feature = "semver")]
     pub fn version(&self) -> semver::Version {
-        semver::Version::parse(&self.version)
+        self.try_version()
             .expect("crate index guarantees a valid semantic version")
     }
+
+    /// Parse and return this crate's version as a `semver::Version`, or the error
+    /// if it isn't valid.
+    #[cfg(feature = "semver")]
+    pub fn try_version(&self) -> Result<semver::Version, semver::Error> {
+        semver::Version::parse(&self.version)
+    }
+
+    /// Return all dependencies whose version requirement isn't valid SemVer, along with the reason.
+    ///
+    /// If there are any, this version of the crate can't be resolved as
+    /// [`Dependency::required_version()`] would panic for each of them.
+    #[cfg(feature = "semver")]
+    pub fn invalid_dependencies(&self) -> impl Iterator<Item = (&Dependency, semver::Error)> {
+        self.dependencies
+            .iter()
+            .filter_map(|dep| dep.try_required_version().err().map(|err| (dep, err)))
+    }
 }
 
 /// A single dependency of a specific crate version
@@ -197,6 +440,10 @@ pub struct Dependency {
     /// The package this crate is contained in
     #[serde(skip_serializing_if = "Option::is_none")]
     pub package: Option<SmolString>,
+    /// The URL of the index of the registry this dependency is obtained from.
+    /// If `None`, it's the same registry as the one of the parent crate.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub registry: Option<SmolString>,
 }
 impl Dependency {
     /// Parse and return this dependency's semantic version requirement as `semver::VersionReq`.
@@ -208,9 +455,17 @@ impl Dependency {
     /// Cargo and crates.io enforce SemVer compliance for dependencies when a crate is
     /// published or updated. Nevertheless, our index contains a small number of releases
     /// whose dependency requirements are not valid SemVer (currently 14 out of ~1.8M).
+    /// Use [`try_required_version()`](Self::try_required_version()) to handle them.
     #[cfg(feature = "semver")]
     pub fn required_version(&self) -> semver::VersionReq {
-        semver::VersionReq::parse(&self.required_version)
+        self.try_required_version()
             .expect("version requirement should always be a valid SemVer")
     }
+
+    /// Parse and return this dependency's semantic version requirement as `semver::VersionReq`,
+    /// or the error if it isn't valid.
+    #[cfg(feature = "semver")]
+    pub fn try_required_version(&self) -> Result<semver::VersionReq, semver::Error> {
+        semver::VersionReq::parse(&self.required_version)
+    }
 }
@@ -0,0 +1,135 @@
+use crates_index_diff::{Change, CrateVersion, RegistryConfig};
+use serde_json::json;
+
+#[test]
+fn serialization_is_tagged_by_kind() -> crate::Result {
+    assert_eq!(
+        serde_json::to_value(Change::Yanked(version("0.1.0")))?,
+        json!({
+            "kind": "yanked",
+            "version": {
+                "name": "gix",
+                "vers": "0.1.0",
+                "yanked": false,
+                "cksum": "0000000000000000000000000000000000000000000000000000000000000000",
+                "features": {},
+                "deps": []
+            }
+        })
+    );
+    let value = serde_json::to_value(Change::CrateDeleted {
+        name: "gix".into(),
+        versions: vec![version("0.1.0")],
+    })?;
+    assert_eq!(value["kind"], "crate_deleted");
+    assert_eq!(value["name"], "gix");
+    assert_eq!(value["versions"][0]["vers"], "0.1.0");
+    Ok(())
+}
+
+#[test]
+fn all_kinds_round_trip() -> crate::Result {
+    let mut yanked = version("0.2.0");
+    yanked.yanked = true;
+    let mut with_rust_version = version("0.1.0");
+    with_rust_version.rust_version = Some("1.70".into());
+    let changes = vec![
+        Change::Added(version("0.1.0")),
+        Change::Unyanked(version("0.1.0")),
+        Change::AddedAndYanked(yanked.clone()),
+        Change::Yanked(yanked),
+        Change::CrateDeleted {
+            name: "gix".into(),
+            versions: vec![version("0.1.0"), version("0.2.0")],
+        },
+        Change::VersionDeleted(version("0.1.0")),
+        Change::Modified {
+            old: version("0.1.0").into(),
+            new: with_rust_version.into(),
+        },
+        Change::ConfigChanged {
+            old: None,
+            new: Some(RegistryConfig {
+                dl: "https://static.crates.io/crates".into(),
+                api: Some("https://crates.io".into()),
+                auth_required: true,
+            }),
+        },
+    ];
+    for change in changes {
+        let json = serde_json::to_string(&change)?;
+        assert_eq!(serde_json::from_str::<Change>(&json)?, change, "{json}");
+    }
+    Ok(())
+}
+
+fn version(version: &str) -> CrateVersion {
+    CrateVersion {
+        name: "gix".into(),
+        version: version.into(),
+        ..Default::default()
+    }
+}
+
+#[test]
+fn compaction_yields_net_effect() {
+    let mut yanked = version("0.1.0");
+    yanked.yanked = true;
+    let mut other = version("0.2.0");
+    other.checksum[0] = 1;
+    let mut modified = other.clone();
+    modified.rust_version = Some("1.70".into());
+    let mut other_yanked = other.clone();
+    other_yanked.yanked = true;
+
+    assert_eq!(
+        Change::compact(vec![
+            Change::Added(version("0.1.0")),
+            Change::Yanked(yanked.clone()),
+            Change::Yanked(other_yanked.clone()),
+        ]),
+        vec![
+            Change::AddedAndYanked(yanked.clone()),
+            Change::Yanked(other_yanked.clone())
+        ]
+    );
+    assert_eq!(
+        Change::compact(vec![
+            Change::Added(version("0.1.0")),
+            Change::Yanked(yanked.clone()),
+            Change::Unyanked(version("0.1.0")),
+            Change::VersionDeleted(version("0.1.0")),
+        ]),
+        vec![],
+        "versions that come and go leave no trace"
+    );
+    assert_eq!(
+        Change::compact(vec![
+            Change::Yanked(yanked.clone()),
+            Change::Unyanked(version("0.1.0")),
+            Change::Modified {
+                old: other.clone().into(),
+                new: modified.clone().into()
+            },
+        ]),
+        vec![Change::Modified {
+            old: other.clone().into(),
+            new: modified.into()
+        }]
+    );
+    assert_eq!(
+        Change::compact(vec![
+            Change::Added(version("0.1.0")),
+            Change::Yanked(other_yanked.clone()),
+            Change::CrateDeleted {
+                name: "gix".into(),
+                versions: vec![version("0.1.0"), other_yanked.clone()],
+            },
+        ]),
+        vec![Change::CrateDeleted {
+            name: "gix".into(),
+            versions: vec![other_yanked],
+        }],
+        "deleting a crate absorbs all changes to versions it deleted"
+    );
+}
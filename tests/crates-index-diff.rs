@@ -1,4 +1,6 @@
 use gix_testtools::Result;
 
+mod change;
 mod index;
+mod sparse;
 mod version;
@@ -1,6 +1,8 @@
-use crate::index::index_ro;
-use crates_index_diff::index::diff::Order;
-use crates_index_diff::{Change, CrateVersion, Index};
+use crate::index::{index_ro, index_ro_with, index_rw};
+use crates_index_diff::index::diff::{CrateFilter, DecodePolicy, Error, Order};
+use crates_index_diff::{Change, CrateVersion, Index, RegistryConfig};
+use std::ops::ControlFlow;
+use std::sync::atomic::AtomicBool;
 
 #[test]
 fn directory_deletions_are_not_picked_up() -> crate::Result {
@@ -38,6 +40,35 @@ fn ancestor_commits_retain_order() -> crate::Result {
     Ok(())
 }
 
+#[test]
+fn ancestor_commits_with_metadata() -> crate::Result {
+    let index = index_ro()?;
+    let repo = index.repository();
+    let from = repo.rev_parse_single("@^{/Yanking crate `gitten#0.3.1`}~1")?;
+    let to = repo.rev_parse_single(":/Yanking crate `gitten#0.3.0`")?;
+    let (commits, order) = index.changes_between_ancestor_commits_with_metadata(from, to)?;
+
+    assert_eq!(order, Order::AsInCratesIndex, "both commits are connected");
+    assert_eq!(commits.len(), 2, "one entry per commit");
+    assert_eq!(commits[1].id, to, "the last commit is the one we asked for");
+    assert_eq!(commits[0].message, "Yanking crate `gitten#0.3.1`\n");
+    assert_eq!(commits[0].changes.len(), 1);
+    assert_eq!(
+        commits[0].changes[0].yanked().expect("yanked").version,
+        "0.3.1"
+    );
+    assert_eq!(commits[1].message, "Yanking crate `gitten#0.3.0`\n");
+    assert_eq!(
+        commits[1].changes[0].yanked().expect("yanked").version,
+        "0.3.0"
+    );
+    assert!(
+        commits[0].time.seconds <= commits[1].time.seconds,
+        "commits are in the order they were made"
+    );
+    Ok(())
+}
+
 #[test]
 fn updates_before_yanks_are_picked_up() -> crate::Result {
     let index = index_ro()?;
@@ -196,6 +227,415 @@ fn normalization() -> crate::Result {
     Ok(())
 }
 
+#[test]
+fn for_each_change_streams_and_stops_early() -> crate::Result {
+    let index = index_ro()?;
+    let from = gix::hash::ObjectId::empty_tree(index.repository().object_hash());
+    let to = index.repository().rev_parse_single("origin/main")?;
+    let mut num_changes = 0;
+    index.for_each_change(from, to, |_change| {
+        num_changes += 1;
+        ControlFlow::Continue(())
+    })?;
+    assert_eq!(
+        num_changes,
+        index.changes_between_commits(from, to)?.len(),
+        "streaming sees the same changes as collecting them"
+    );
+
+    let mut num_changes = 0;
+    index.for_each_change(from, to, |_change| {
+        num_changes += 1;
+        if num_changes == 10 {
+            ControlFlow::Break(())
+        } else {
+            ControlFlow::Continue(())
+        }
+    })?;
+    assert_eq!(num_changes, 10, "breaking stops the diff without error");
+
+    let from = index
+        .repository()
+        .rev_parse_single("@^{/Yanking crate `gitten#0.3.1`}~1")?;
+    let to = index
+        .repository()
+        .rev_parse_single(":/Yanking crate `gitten#0.3.0`")?;
+    let mut versions = Vec::new();
+    let order = index.for_each_change_between_ancestor_commits(from, to, |change| {
+        versions.push(change.versions()[0].version.clone());
+        ControlFlow::Break(())
+    })?;
+    assert_eq!(order, Order::AsInCratesIndex);
+    assert_eq!(
+        versions,
+        ["0.3.1"],
+        "the first change in commit order is seen and nothing after it"
+    );
+    Ok(())
+}
+
+#[test]
+fn malformed_lines_can_be_skipped_and_reported() -> crate::Result {
+    let (index, tmp) = index_rw()?;
+    let repo = index.repository();
+    let tree_with_crate_file = |content: &str| -> crate::Result<gix::hash::ObjectId> {
+        let (mut id, mut kind) = (
+            repo.write_blob(content)?.detach(),
+            gix::objs::tree::EntryKind::Blob,
+        );
+        for name in ["abcd", "cd", "ab"] {
+            id = repo
+                .write_object(gix::objs::Tree {
+                    entries: vec![gix::objs::tree::Entry {
+                        mode: kind.into(),
+                        filename: name.into(),
+                        oid: id,
+                    }],
+                })?
+                .detach();
+            kind = gix::objs::tree::EntryKind::Tree;
+        }
+        Ok(id)
+    };
+    let version = |version: &str, checksum: char| {
+        format!(
+            r#"{{"name":"abcd","vers":"{version}","deps":[],"cksum":"{}","features":{{}},"yanked":false}}"#,
+            checksum.to_string().repeat(64)
+        )
+    };
+    let (v1, v2) = (version("0.1.0", '1'), version("0.2.0", '2'));
+    let empty = gix::hash::ObjectId::empty_tree(repo.object_hash());
+    let malformed = tree_with_crate_file(&format!("{v1}\nnot json\n"))?;
+    let valid = tree_with_crate_file(&format!("{v1}\n{v2}\n"))?;
+    let corrupted = tree_with_crate_file(&format!("{v1}\n{}\n", &v2[..v2.len() - 1]))?;
+
+    assert!(
+        matches!(
+            index.changes_between_commits(empty, malformed),
+            Err(Error::VersionDecode { .. })
+        ),
+        "by default, malformed lines are an error"
+    );
+
+    let index = Index::builder(tmp.path())
+        .decode_policy(DecodePolicy::SkipAndReport)
+        .build()?;
+    let outcome = index.changes_between_commits_with_outcome(
+        empty,
+        malformed,
+        Order::ImplementationDefined,
+        gix::progress::Discard,
+        &AtomicBool::default(),
+    )?;
+    assert_eq!(outcome.changes.len(), 1, "the valid line is still seen");
+    assert_eq!(outcome.changes[0].added().expect("added").version, "0.1.0");
+    assert_eq!(outcome.diagnostics.len(), 1);
+    assert_eq!(outcome.diagnostics[0].file_name, "abcd");
+    assert_eq!(outcome.diagnostics[0].line_number, 2);
+    assert_eq!(outcome.diagnostics[0].line, "not json");
+    assert_eq!(
+        index.changes_between_commits(empty, malformed)?,
+        outcome.changes,
+        "other methods skip the line silently"
+    );
+
+    let outcome = index.changes_between_commits_with_outcome(
+        valid,
+        corrupted,
+        Order::ImplementationDefined,
+        gix::progress::Discard,
+        &AtomicBool::default(),
+    )?;
+    assert!(
+        outcome.changes.is_empty(),
+        "a version whose line can't be decoded anymore isn't reported as deleted"
+    );
+    assert_eq!(outcome.diagnostics.len(), 1);
+    assert_eq!(outcome.diagnostics[0].line_number, 2);
+    Ok(())
+}
+
+#[test]
+fn config_changes_are_picked_up() -> crate::Result {
+    let (index, tmp) = index_rw()?;
+    let repo = index.repository();
+    let tree_with_config = |config: &str| -> crate::Result<gix::hash::ObjectId> {
+        let config = repo.write_blob(config)?.detach();
+        Ok(repo
+            .write_object(gix::objs::Tree {
+                entries: vec![gix::objs::tree::Entry {
+                    mode: gix::objs::tree::EntryKind::Blob.into(),
+                    filename: "config.json".into(),
+                    oid: config,
+                }],
+            })?
+            .detach())
+    };
+    let empty = gix::hash::ObjectId::empty_tree(repo.object_hash());
+    let old = tree_with_config(r#"{"dl":"https://example.com/dl","api":"https://example.com"}"#)?;
+    let reformatted =
+        tree_with_config(r#"{ "dl": "https://example.com/dl", "api": "https://example.com" }"#)?;
+    let new = tree_with_config(r#"{"dl":"https://example.org/dl","auth-required":true}"#)?;
+    let broken = tree_with_config("{")?;
+    let nested = repo
+        .write_object(gix::objs::Tree {
+            entries: vec![
+                gix::objs::tree::Entry {
+                    mode: gix::objs::tree::EntryKind::Tree.into(),
+                    filename: "ab".into(),
+                    oid: new,
+                },
+                gix::objs::tree::Entry {
+                    mode: gix::objs::tree::EntryKind::Tree.into(),
+                    filename: "cd".into(),
+                    oid: broken,
+                },
+                repo.find_object(old)?.into_tree().decode()?.entries[0].into(),
+            ],
+        })?
+        .detach();
+    let old_config = RegistryConfig {
+        dl: "https://example.com/dl".into(),
+        api: Some("https://example.com".into()),
+        auth_required: false,
+    };
+    let new_config = RegistryConfig {
+        dl: "https://example.org/dl".into(),
+        api: None,
+        auth_required: true,
+    };
+
+    for threads in [1, 4] {
+        let index = Index::builder(tmp.path()).diff_threads(threads).build()?;
+        assert_eq!(
+            index.changes_between_commits(empty, old)?,
+            vec![Change::ConfigChanged {
+                old: None,
+                new: Some(old_config.clone())
+            }]
+        );
+        assert_eq!(
+            index.changes_between_commits(old, new)?,
+            vec![Change::ConfigChanged {
+                old: Some(old_config.clone()),
+                new: Some(new_config.clone())
+            }]
+        );
+        assert!(
+            index.changes_between_commits(old, reformatted)?.is_empty(),
+            "changes in formatting only are ignored"
+        );
+        assert!(
+            index.changes_between_commits(old, nested)?.is_empty(),
+            "only the configuration in the root of the index is the registry configuration"
+        );
+    }
+
+    assert!(matches!(
+        index.changes_between_commits(old, broken),
+        Err(Error::ConfigDecode(_))
+    ));
+    let index = Index::builder(tmp.path())
+        .decode_policy(DecodePolicy::SkipAndReport)
+        .build()?;
+    let outcome = index.changes_between_commits_with_outcome(
+        old,
+        broken,
+        Order::ImplementationDefined,
+        gix::progress::Discard,
+        &AtomicBool::default(),
+    )?;
+    assert_eq!(
+        outcome.changes,
+        vec![Change::ConfigChanged {
+            old: Some(old_config),
+            new: None
+        }],
+        "the broken configuration is treated as missing"
+    );
+    assert_eq!(outcome.diagnostics.len(), 1);
+    assert_eq!(outcome.diagnostics[0].file_name, "config.json");
+    Ok(())
+}
+
+#[test]
+fn diffs_can_be_interrupted() -> crate::Result {
+    let index = index_ro()?;
+    let from = gix::hash::ObjectId::empty_tree(index.repository().object_hash());
+    let to = index.repository().rev_parse_single("origin/main")?.detach();
+    let ancestor = index
+        .repository()
+        .rev_parse_single("origin/main~10")?
+        .detach();
+    let interrupt = AtomicBool::new(true);
+    for threads in [1, 4] {
+        let index = index_ro_with(|builder| builder.diff_threads(threads))?;
+        assert!(matches!(
+            index.changes_between_commits_with_options(
+                from,
+                to,
+                gix::progress::Discard,
+                &interrupt
+            ),
+            Err(Error::Interrupted)
+        ));
+        assert!(matches!(
+            index.changes_between_ancestor_commits_with_options(
+                ancestor,
+                to,
+                gix::progress::Discard,
+                &interrupt
+            ),
+            Err(Error::Interrupted)
+        ));
+        assert!(matches!(
+            index.changes_between_ancestor_commits_with_metadata_and_options(
+                ancestor,
+                to,
+                gix::progress::Discard,
+                &interrupt
+            ),
+            Err(Error::Interrupted)
+        ));
+    }
+    assert_eq!(
+        index
+            .changes_between_commits_with_options(
+                ancestor,
+                to,
+                gix::progress::Discard,
+                &AtomicBool::default()
+            )?
+            .len(),
+        index.changes_between_commits(ancestor, to)?.len()
+    );
+    Ok(())
+}
+
+#[test]
+fn crate_filter_skips_other_crates() -> crate::Result {
+    let index = index_ro()?;
+    let from = index
+        .repository()
+        .rev_parse_single("origin/main~40")?
+        .detach();
+    let to = index.repository().rev_parse_single("origin/main")?.detach();
+    let all = index.changes_between_commits(from, to)?;
+    let name = all[0].versions()[0].name.to_string();
+    let expected: Vec<_> = all
+        .iter()
+        .filter(|change| change.versions()[0].name.eq_ignore_ascii_case(&name))
+        .cloned()
+        .collect();
+
+    let index =
+        index_ro_with(|builder| builder.crate_filter(CrateFilter::names([name.to_uppercase()])))?;
+    assert_eq!(
+        index.changes_between_commits(from, to)?,
+        expected,
+        "names are matched case-insensitively"
+    );
+
+    let prefix = name.to_ascii_lowercase()[..1].to_owned();
+    let index = index_ro_with(|builder| {
+        builder.crate_filter(CrateFilter::prefix(prefix.to_ascii_uppercase()))
+    })?;
+    let changes = index.changes_between_commits(from, to)?;
+    assert!(
+        !changes.is_empty(),
+        "prefixes are matched case-insensitively"
+    );
+    assert!(changes.iter().all(|change| change.versions()[0]
+        .name
+        .to_ascii_lowercase()
+        .starts_with(&prefix)));
+
+    let index =
+        index_ro_with(|builder| builder.crate_filter(CrateFilter::predicate(|_name| false)))?;
+    assert!(index.changes_between_commits(from, to)?.is_empty());
+    Ok(())
+}
+
+#[test]
+#[cfg(feature = "parallel")]
+fn parallel_diff_finds_the_same_changes() -> crate::Result {
+    let index = index_ro_with(|builder| builder.diff_threads(1))?;
+    let from = gix::hash::ObjectId::empty_tree(index.repository().object_hash());
+    let to = index.repository().rev_parse_single("origin/main")?.detach();
+    let summarize = |changes: Vec<Change>| {
+        let mut changes: Vec<_> = changes
+            .iter()
+            .map(|change| {
+                let versions: Vec<_> = change
+                    .versions()
+                    .iter()
+                    .map(|v| (v.name.clone(), v.version.clone()))
+                    .collect();
+                (change.to_string(), versions)
+            })
+            .collect();
+        changes.sort();
+        changes
+    };
+
+    let serial = summarize(index.changes_between_commits(from, to)?);
+    let index = index_ro_with(|builder| builder.diff_threads(4))?;
+    let parallel = summarize(index.changes_between_commits(from, to)?);
+    assert_eq!(parallel, serial, "the same changes are found");
+
+    let mut num_changes = 0;
+    index.for_each_change(from, to, |_change| {
+        num_changes += 1;
+        if num_changes == 10 {
+            ControlFlow::Break(())
+        } else {
+            ControlFlow::Continue(())
+        }
+    })?;
+    assert_eq!(num_changes, 10, "breaking stops all threads without error");
+    Ok(())
+}
+
+#[test]
+#[cfg(feature = "parallel")]
+fn parallel_ancestor_diffs_retain_order() -> crate::Result {
+    let index = index_ro_with(|builder| builder.diff_threads(1))?;
+    let from = index
+        .repository()
+        .rev_parse_single("origin/main~40")?
+        .detach();
+    let to = index.repository().rev_parse_single("origin/main")?.detach();
+
+    let (serial, order) = index.changes_between_ancestor_commits(from, to)?;
+    assert_eq!(order, Order::AsInCratesIndex);
+    let serial_commits = index
+        .changes_between_ancestor_commits_with_metadata(from, to)?
+        .0;
+    let index = index_ro_with(|builder| builder.diff_threads(4))?;
+    let (parallel, order) = index.changes_between_ancestor_commits(from, to)?;
+    assert_eq!(order, Order::AsInCratesIndex);
+    assert_eq!(parallel, serial, "the order is exactly the same");
+    let parallel_commits = index
+        .changes_between_ancestor_commits_with_metadata(from, to)?
+        .0;
+    assert_eq!(
+        parallel_commits.iter().map(|c| c.id).collect::<Vec<_>>(),
+        serial_commits.iter().map(|c| c.id).collect::<Vec<_>>()
+    );
+
+    let mut num_changes = 0;
+    index.for_each_change_between_ancestor_commits(from, to, |_change| {
+        num_changes += 1;
+        if num_changes == 10 {
+            ControlFlow::Break(())
+        } else {
+            ControlFlow::Continue(())
+        }
+    })?;
+    assert_eq!(num_changes, 10, "breaking stops all threads without error");
+    Ok(())
+}
+
 fn changes(mut index: Index, revspec: &str) -> crate::Result<Vec<Change>> {
     let (prev, current) = {
         let repo = index.repository_mut();
@@ -1,4 +1,5 @@
-use crates_index_diff::index::diff::Order;
+use crates_index_diff::index::diff::{Order, StartFrom};
+use crates_index_diff::index::init::IndexBuilder;
 use crates_index_diff::Index;
 use gix::refs::transaction::PreviousValue;
 use gix_testtools::tempfile::TempDir;
@@ -6,13 +7,19 @@ use std::path::PathBuf;
 use std::sync::atomic::AtomicBool;
 
 mod changes_between_commits;
+mod snapshot;
+mod squash;
 
 const NUM_CHANGES_SINCE_EVER: usize = 3521;
 
 #[test]
 fn peek_changes() -> crate::Result {
-    let mut index = index_ro()?;
-    index.branch_name = "main";
+    let index = index_ro()?;
+    assert_eq!(
+        index.branch_name(),
+        "main",
+        "the branch is detected from HEAD"
+    );
     for order in [Order::ImplementationDefined, Order::AsInCratesIndex] {
         assert!(
             index.last_seen_reference().is_err(),
@@ -65,6 +72,177 @@ fn clone_if_needed() {
     .expect("second instance re-uses existing clone");
 }
 
+#[test]
+fn default_branch_is_detected() -> crate::Result {
+    use gix::refs::transaction::{Change, LogChange, RefEdit};
+    use std::convert::TryInto;
+    let tmp = TempDir::new()?;
+    let index = Index::from_path_or_cloned_with_options(
+        tmp.path(),
+        gix::progress::Discard,
+        &AtomicBool::default(),
+        clone_options(),
+    )?;
+    assert_eq!(index.branch_name(), "main", "the remote advertises `main`");
+    assert_eq!(index.default_branch_name(), Some("main"));
+
+    let repo = index.repository();
+    let main = repo.rev_parse_single("refs/remotes/origin/main")?.detach();
+    repo.reference(
+        "refs/remotes/origin/trunk",
+        main,
+        PreviousValue::MustNotExist,
+        "new default branch",
+    )?;
+    repo.edit_reference(RefEdit {
+        change: Change::Update {
+            log: LogChange::default(),
+            expected: PreviousValue::Any,
+            new: gix::refs::Target::Symbolic("refs/remotes/origin/trunk".try_into()?),
+        },
+        name: "refs/remotes/origin/HEAD".try_into()?,
+        deref: false,
+    })?;
+
+    let index = Index::builder(tmp.path()).build()?;
+    assert_eq!(
+        index.branch_name(),
+        "trunk",
+        "the remote HEAD takes precedence"
+    );
+    let index = Index::builder(tmp.path()).branch_name("main").build()?;
+    assert_eq!(index.branch_name(), "main", "configuration wins");
+    assert_eq!(
+        index.default_branch_name(),
+        Some("trunk"),
+        "the difference can be observed"
+    );
+    Ok(())
+}
+
+#[test]
+fn shallow_clone_can_fetch_changes() -> crate::Result {
+    let tmp = TempDir::new()?;
+    let index = Index::builder(tmp.path())
+        .url(clone_options().url)
+        .depth(std::num::NonZeroU32::new(5).expect("non-zero"))
+        .build()?;
+    let repo = index.repository();
+    assert!(
+        repo.is_shallow(),
+        "only the most recent commits are present"
+    );
+    let boundary = repo
+        .shallow_commits()?
+        .expect("shallow clones have a boundary")
+        .first()
+        .copied()
+        .expect("one commit per branch");
+    let tip = repo.rev_parse_single("refs/remotes/origin/main")?.detach();
+
+    let (changes, order) = index.changes_between_ancestor_commits(boundary, tip)?;
+    assert_eq!(
+        order,
+        Order::AsInCratesIndex,
+        "the walk from the tip stops at the boundary, which is the ancestor"
+    );
+    assert_eq!(
+        index
+            .changes_between_ancestor_commits_with_metadata(boundary, tip)?
+            .0
+            .len(),
+        4,
+        "each commit after the boundary is diffed individually"
+    );
+
+    assert!(
+        !index.fetch_changes()?.is_empty(),
+        "the first fetch sees everything up to the tip"
+    );
+    index
+        .last_seen_reference()?
+        .set_target_id(boundary, "reset to boundary")?;
+    assert_eq!(
+        index.fetch_changes_with_options(
+            gix::progress::Discard,
+            &AtomicBool::default(),
+            Order::AsInCratesIndex
+        )?,
+        changes,
+        "subsequent fetches work within the shallow history"
+    );
+    assert_eq!(index.last_seen_reference()?.id(), tip);
+    Ok(())
+}
+
+#[test]
+fn depth_and_shallow_since_are_exclusive() -> crate::Result {
+    let tmp = TempDir::new()?;
+    let res = Index::from_path_or_cloned_with_options(
+        tmp.path(),
+        gix::progress::Discard,
+        &AtomicBool::default(),
+        crates_index_diff::index::CloneOptions {
+            depth: std::num::NonZeroU32::new(1),
+            shallow_since: Some(gix::date::Time::new(0, 0)),
+            ..clone_options()
+        },
+    );
+    assert!(matches!(
+        res,
+        Err(crates_index_diff::index::init::Error::ConflictingShallowOptions)
+    ));
+    Ok(())
+}
+
+#[test]
+fn start_from_latest_skips_history() -> crate::Result {
+    let tmp = TempDir::new()?;
+    let index = Index::builder(tmp.path())
+        .url(clone_options().url)
+        .start_from(StartFrom::Latest)
+        .build()?;
+    for order in [Order::ImplementationDefined, Order::AsInCratesIndex] {
+        let (changes, _to) = index.peek_changes_with_options(
+            gix::progress::Discard,
+            &AtomicBool::default(),
+            order,
+        )?;
+        assert!(
+            changes.is_empty(),
+            "nothing was seen, so there is nothing new"
+        );
+    }
+    assert!(
+        index.last_seen_reference().is_err(),
+        "peeking changes nothing"
+    );
+
+    let docs = index.cursor("docs-builder")?;
+    assert!(docs
+        .fetch(
+            gix::progress::Discard,
+            &AtomicBool::default(),
+            Order::AsInCratesIndex
+        )?
+        .is_empty());
+    assert!(index.fetch_changes()?.is_empty());
+    let tip = index.last_seen_reference()?.id().detach();
+    assert_eq!(docs.position(), Some(tip), "the latest state is seen now");
+
+    let previous = index
+        .repository()
+        .rev_parse_single(format!("{tip}~1").as_str())?
+        .detach();
+    index.set_last_seen_reference(previous)?;
+    assert_eq!(
+        index.fetch_changes()?.len(),
+        2,
+        "once something was seen, changes are provided as usual"
+    );
+    Ok(())
+}
+
 #[test]
 fn changes_since_last_fetch() {
     let (mut index, _tmp) = index_rw().unwrap();
@@ -88,7 +266,7 @@ fn changes_since_last_fetch() {
     // reset to previous one
     marker
         .set_target_id(
-            repo.rev_parse(format!("{}~1", index.seen_ref_name).as_str())
+            repo.rev_parse(format!("{}~1", index.seen_ref_name()).as_str())
                 .unwrap()
                 .single()
                 .unwrap(),
@@ -161,20 +339,147 @@ fn changes_since_last_fetch() {
     );
 }
 
+#[test]
+#[cfg(feature = "async")]
+fn changes_since_last_fetch_async() -> crate::Result {
+    let (index, _tmp) = index_rw()?;
+    let runtime = tokio::runtime::Builder::new_current_thread().build()?;
+    let interrupt = std::sync::Arc::new(AtomicBool::default());
+
+    let (changes, to) =
+        runtime
+            .block_on(runtime.spawn(
+                index.peek_changes_async(interrupt.clone(), Order::ImplementationDefined),
+            ))??;
+    assert_eq!(changes.len(), NUM_CHANGES_SINCE_EVER);
+    assert!(
+        index.last_seen_reference().is_err(),
+        "peeking doesn't create the marker"
+    );
+
+    let changes =
+        runtime.block_on(index.fetch_changes_async(interrupt.clone(), Order::AsInCratesIndex))?;
+    assert_eq!(changes.len(), NUM_CHANGES_SINCE_EVER);
+    assert_eq!(index.last_seen_reference()?.id(), to);
+    assert!(runtime
+        .block_on(index.fetch_changes_async(interrupt, Order::AsInCratesIndex))?
+        .is_empty());
+    Ok(())
+}
+
+#[test]
+fn cursors_advance_independently() -> crate::Result {
+    let (index, _tmp) = index_rw()?;
+    assert!(
+        index.cursors()?.is_empty(),
+        "no cursor was acknowledged yet"
+    );
+    assert!(
+        index.cursor("no..dots").is_err(),
+        "cursor names must be valid in reference names"
+    );
+
+    let to = index.fetch_remote(gix::progress::Discard, &AtomicBool::default())?;
+    let docs = index.cursor("docs-builder")?;
+    let search = index.cursor("search")?;
+    assert_eq!(docs.position(), None);
+    assert_eq!(
+        docs.peek(to, Order::ImplementationDefined)?.len(),
+        NUM_CHANGES_SINCE_EVER
+    );
+    docs.ack(to)?;
+    assert_eq!(docs.position(), Some(to));
+    assert_eq!(docs.peek(to, Order::AsInCratesIndex)?.len(), 0);
+    assert_eq!(
+        search.peek(to, Order::AsInCratesIndex)?.len(),
+        NUM_CHANGES_SINCE_EVER,
+        "other cursors are unaffected"
+    );
+    assert!(
+        index.last_seen_reference().is_err(),
+        "cursors don't affect the last-seen reference"
+    );
+
+    let previous = index
+        .repository()
+        .rev_parse_single(format!("{to}~1").as_str())?
+        .detach();
+    search.ack(previous)?;
+    assert_eq!(index.cursors()?, ["docs-builder", "search"]);
+    assert_eq!(
+        search.fetch(
+            gix::progress::Discard,
+            &AtomicBool::default(),
+            Order::AsInCratesIndex
+        )?,
+        index.changes_between_ancestor_commits(previous, to)?.0,
+        "fetching catches up from the last acknowledged position"
+    );
+    assert_eq!(search.position(), Some(to));
+    Ok(())
+}
+
+#[test]
+fn acknowledge_is_compare_and_swap() -> crate::Result {
+    use crates_index_diff::index::diff::Error;
+    let (index, _tmp) = index_rw()?;
+    let to = index.fetch_remote(gix::progress::Discard, &AtomicBool::default())?;
+    let previous = index
+        .repository()
+        .rev_parse_single(format!("{to}~1").as_str())?
+        .detach();
+
+    assert!(matches!(
+        index.acknowledge(Some(previous), to),
+        Err(Error::AcknowledgeConflict { actual: None, .. })
+    ));
+    index.acknowledge(None, previous)?;
+    assert!(
+        matches!(
+            index.acknowledge(None, to),
+            Err(Error::AcknowledgeConflict { actual: Some(actual), .. }) if actual == previous
+        ),
+        "another process acknowledged already"
+    );
+    index.acknowledge(Some(previous), to)?;
+    assert_eq!(index.last_seen_reference()?.id(), to);
+
+    assert!(matches!(
+        index.acknowledge(Some(to), previous),
+        Err(Error::NonFastForward { .. })
+    ));
+    assert_eq!(
+        index.last_seen_reference()?.id(),
+        to,
+        "the reference doesn't move backwards"
+    );
+    index.acknowledge_forced(Some(to), previous)?;
+    assert_eq!(index.last_seen_reference()?.id(), previous);
+
+    let docs = index.cursor("docs-builder")?;
+    docs.acknowledge(None, to)?;
+    assert!(matches!(
+        docs.acknowledge(None, to),
+        Err(Error::AcknowledgeConflict { .. })
+    ));
+    assert_eq!(docs.position(), Some(to));
+    Ok(())
+}
+
 fn index_ro() -> crate::Result<Index> {
+    index_ro_with(|builder| builder)
+}
+
+fn index_ro_with(configure: impl FnOnce(IndexBuilder) -> IndexBuilder) -> crate::Result<Index> {
     let dir = fixture_dir()?;
-    Ok(Index::from_path_or_cloned(dir.join("clone"))?)
+    Ok(configure(Index::builder(dir.join("clone"))).build()?)
 }
 
 fn index_rw() -> crate::Result<(Index, TempDir)> {
     let tmp = TempDir::new().unwrap();
-    let mut index = Index::from_path_or_cloned_with_options(
-        tmp.path(),
-        gix::progress::Discard,
-        &AtomicBool::default(),
-        clone_options(),
-    )?;
-    index.branch_name = "main";
+    let index = Index::builder(tmp.path())
+        .url(clone_options().url)
+        .build()?;
     Ok((index, tmp))
 }
 
@@ -190,5 +495,6 @@ fn fixture_dir() -> crate::Result<PathBuf> {
 fn clone_options() -> crates_index_diff::index::CloneOptions {
     crates_index_diff::index::CloneOptions {
         url: fixture_dir().unwrap().join("base").display().to_string(),
+        ..Default::default()
     }
 }
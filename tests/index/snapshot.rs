@@ -0,0 +1,58 @@
+use crate::index::{index_ro, index_rw};
+use std::sync::atomic::AtomicBool;
+
+#[test]
+fn crates_and_versions_at_revision() -> crate::Result {
+    let index = index_ro()?;
+    let snapshot = index.snapshot(":/Yanking crate `gitten#0.3.0`")?;
+    let gitten = snapshot.find_crate("GitTen")?.expect("present");
+    assert_eq!(gitten.name(), "gitten", "lookup is case-insensitive");
+    assert!(gitten.version("0.3.0")?.expect("present").yanked);
+    assert!(gitten.version("0.3.1")?.expect("present").yanked);
+    assert_eq!(gitten.version("99.0.0")?, None);
+    assert_eq!(snapshot.find_crate("does-not-exist")?, None);
+    assert!(
+        index.snapshot("HEAD~2..HEAD").is_err(),
+        "ranges can't be looked at"
+    );
+
+    let before = index.snapshot("@^{/Yanking crate `gitten#0.3.1`}~1")?;
+    let gitten_before = before.find_crate("gitten")?.expect("present");
+    assert!(!gitten_before.version("0.3.0")?.expect("present").yanked);
+    assert_eq!(
+        gitten_before.versions().count(),
+        gitten.versions().count(),
+        "yanking doesn't remove versions"
+    );
+
+    let crates = snapshot.crates()?.collect::<Result<Vec<_>, _>>()?;
+    assert!(crates.iter().any(|c| c.name() == "gitten"));
+    assert!(
+        crates.iter().all(|c| c.versions().all(|v| v.is_ok())),
+        "all versions can be parsed"
+    );
+    Ok(())
+}
+
+#[test]
+fn fetch_snapshot_marks_latest_state_as_seen() -> crate::Result {
+    let (index, _tmp) = index_rw()?;
+    let snapshot =
+        index.fetch_snapshot_with_options(gix::progress::Discard, &AtomicBool::default())?;
+    let seen = index.last_seen_reference()?.id().detach();
+    assert_eq!(
+        seen,
+        index
+            .repository()
+            .rev_parse_single("refs/remotes/origin/main")?
+            .detach(),
+        "the latest fetched state is seen"
+    );
+    assert_eq!(
+        snapshot.tree_id(),
+        index.snapshot(seen.to_string())?.tree_id()
+    );
+    assert!(snapshot.find_crate("gitten")?.is_some());
+    assert!(index.fetch_changes()?.is_empty(), "nothing changed since");
+    Ok(())
+}
@@ -0,0 +1,132 @@
+use crate::index::index_rw;
+use crates_index_diff::index::diff::{Error, Order};
+use crates_index_diff::Index;
+use gix::refs::transaction::PreviousValue;
+use std::sync::atomic::AtomicBool;
+
+#[test]
+fn squashed_history_is_detected_and_diffed_through_snapshot_branch() -> crate::Result {
+    let (index, tmp) = index_rw()?;
+    let repo = index.repository();
+    let rev = |spec: &str| -> crate::Result<gix::hash::ObjectId> {
+        Ok(repo.rev_parse_single(spec)?.detach())
+    };
+    let (last_seen, squashed_at, tip) = (
+        rev("origin/main~20")?,
+        rev("origin/main~10")?,
+        rev("origin/main")?,
+    );
+    assert_eq!(index.detect_squash(last_seen, tip)?, None, "linear history");
+    assert_eq!(
+        index.detect_squash(tip, last_seen)?,
+        None,
+        "moving backwards isn't squashing"
+    );
+    assert_eq!(
+        index.detect_squash(gix::hash::ObjectId::empty_tree(repo.object_hash()), tip)?,
+        None,
+        "nothing was seen yet"
+    );
+
+    let squasher = gix::actor::SignatureRef {
+        name: "squasher".into(),
+        email: "squasher@example.com".into(),
+        time: "4102444800 +0000",
+    };
+    let tree = |id: gix::hash::ObjectId| -> crate::Result<gix::hash::ObjectId> {
+        Ok(repo.find_object(id)?.peel_to_tree()?.id)
+    };
+    let new_root = repo
+        .new_commit_as(
+            squasher,
+            squasher,
+            "squash",
+            tree(squashed_at)?,
+            None::<gix::hash::ObjectId>,
+        )?
+        .id;
+    let new_tip = repo
+        .new_commit_as(squasher, squasher, "update", tree(tip)?, Some(new_root))?
+        .id;
+
+    let squash = index
+        .detect_squash(last_seen, new_tip)?
+        .expect("last seen commit isn't an ancestor anymore");
+    assert_eq!(squash.last_seen, last_seen);
+    assert_eq!(squash.new_root, new_root);
+    assert_eq!(
+        repo.find_commit(squash.old_root)?.parent_ids().count(),
+        0,
+        "the old root is the first commit of the fixture"
+    );
+    assert_eq!(squash.snapshot, None, "no snapshot branch is available yet");
+    let (changes, order) = index.changes_through_squash(&squash, new_tip)?;
+    assert_eq!(order, Order::ImplementationDefined);
+    assert_eq!(changes, index.changes_between_commits(last_seen, tip)?);
+    let cursor = index.cursor("consumer")?;
+    cursor.ack(last_seen)?;
+    let outcome = cursor.peek_with_outcome(new_tip, Order::AsInCratesIndex)?;
+    assert_eq!(
+        outcome.order,
+        Order::ImplementationDefined,
+        "ordered changes can't be provided without snapshot branch"
+    );
+    assert_eq!(outcome.squash, Some(squash));
+    assert_eq!(outcome.changes, changes);
+    let strict_index = Index::builder(tmp.path()).fail_on_squash(true).build()?;
+    assert!(
+        matches!(
+            strict_index.cursor("consumer")?.peek(new_tip, Order::AsInCratesIndex),
+            Err(Error::Squashed(squash)) if squash.new_root == new_root
+        ),
+        "failing is opt-in"
+    );
+
+    repo.reference(
+        "refs/remotes/origin/snapshot-2100-01-01",
+        squashed_at,
+        PreviousValue::MustNotExist,
+        "snapshot of history before squash",
+    )?;
+    let squash = index
+        .detect_squash(last_seen, new_tip)?
+        .expect("still squashed");
+    let snapshot = squash.snapshot.clone().expect("snapshot branch was found");
+    assert_eq!(
+        snapshot.name.as_bstr(),
+        "refs/remotes/origin/snapshot-2100-01-01"
+    );
+    assert_eq!(snapshot.tip, squashed_at);
+
+    let (changes, order) = index.changes_through_squash(&squash, new_tip)?;
+    assert_eq!(order, Order::AsInCratesIndex);
+    let mut expected = index
+        .changes_between_ancestor_commits(last_seen, squashed_at)?
+        .0;
+    expected.extend(index.changes_between_commits(squashed_at, tip)?);
+    assert_eq!(changes, expected);
+    assert!(matches!(
+        index.changes_through_squash_with_options(
+            &squash,
+            new_tip,
+            gix::progress::Discard,
+            &AtomicBool::new(true)
+        ),
+        Err(Error::Interrupted)
+    ));
+    let outcome = cursor.peek_with_outcome(new_tip, Order::AsInCratesIndex)?;
+    assert_eq!(
+        outcome.changes, expected,
+        "ordered changes go through the snapshot branch"
+    );
+    assert_eq!(outcome.order, Order::AsInCratesIndex);
+    assert_eq!(outcome.squash, Some(squash));
+    assert!(
+        strict_index
+            .cursor("consumer")?
+            .peek(new_tip, Order::AsInCratesIndex)
+            .is_ok(),
+        "there is no need to fail with a snapshot branch"
+    );
+    Ok(())
+}
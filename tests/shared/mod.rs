@@ -133,6 +133,12 @@ pub fn baseline(mode: Step) -> Result<(), Box<dyn std::error::Error + Send + Syn
                             VersionDeleted(v) => {
                                 versions.remove(&v.checksum);
                             }
+                            Modified { .. } => {
+                                // neither the checksum nor the yanked state changed
+                            }
+                            ConfigChanged { .. } => {
+                                // the registry configuration doesn't affect crate versions
+                            }
                         }
                     }
                     let elapsed = start.elapsed().as_secs_f32();
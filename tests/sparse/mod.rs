@@ -0,0 +1,205 @@
+use crates_index_diff::sparse::SparseIndex;
+use crates_index_diff::Change;
+use gix_testtools::tempfile::TempDir;
+use std::hash::{Hash, Hasher};
+use std::io::{BufRead, BufReader, Write};
+use std::net::TcpListener;
+use std::path::{Path, PathBuf};
+
+const V1: &str = r#"{"name":"gix","vers":"0.1.0","deps":[],"cksum":"0000000000000000000000000000000000000000000000000000000000000001","features":{},"yanked":false}"#;
+const V2: &str = r#"{"name":"gix","vers":"0.2.0","deps":[],"cksum":"0000000000000000000000000000000000000000000000000000000000000002","features":{},"yanked":false}"#;
+const V2_YANKED: &str = r#"{"name":"gix","vers":"0.2.0","deps":[],"cksum":"0000000000000000000000000000000000000000000000000000000000000002","features":{},"yanked":true}"#;
+const V1_WITH_RUST_VERSION: &str = r#"{"name":"gix","vers":"0.1.0","deps":[],"cksum":"0000000000000000000000000000000000000000000000000000000000000001","features":{},"yanked":false,"rust_version":"1.70"}"#;
+const CONFIG: &str = r#"{"dl":"https://static.crates.io/crates"}"#;
+const V3: &str = r#"{"name":"gix","vers":"0.3.0","deps":[],"cksum":"0000000000000000000000000000000000000000000000000000000000000003","features":{},"yanked":false}"#;
+
+#[test]
+fn changes_are_detected_per_crate_file() -> crate::Result {
+    let served = TempDir::new()?;
+    let state = TempDir::new()?;
+    write(served.path(), "config.json", CONFIG)?;
+    write(served.path(), "3/g/gix", &lines(&[V1, V2]))?;
+    let index = SparseIndex::with_url(serve(served.path().to_owned())?, state.path())?;
+
+    let (changes, _seen) = index.peek_changes(["gix", "unknown"])?;
+    assert_eq!(
+        changes.len(),
+        3,
+        "the configuration and all versions are new"
+    );
+    assert!(
+        matches!(&changes[0], Change::ConfigChanged { old: None, new: Some(config) } if config.dl == "https://static.crates.io/crates")
+    );
+    assert!(changes[1..].iter().all(|c| c.added().is_some()));
+    assert_eq!(
+        index.peek_changes(["gix"])?.0.len(),
+        3,
+        "peeking doesn't mark anything as seen"
+    );
+
+    assert_eq!(index.fetch_changes(["gix"])?.len(), 3);
+    assert_eq!(
+        index.fetch_changes(["gix"])?.len(),
+        0,
+        "nothing changed, so the server responds with 'not modified'"
+    );
+
+    write(served.path(), "3/g/gix", &lines(&[V1, V2_YANKED, V3]))?;
+    write(
+        served.path(),
+        "config.json",
+        r#"{"dl":"https://example.com/crates","api":"https://example.com"}"#,
+    )?;
+    let changes = index.fetch_changes(["gix"])?;
+    assert_eq!(changes.len(), 3);
+    assert!(
+        matches!(&changes[0], Change::ConfigChanged { old: Some(_), new: Some(config) } if config.api.as_deref() == Some("https://example.com")),
+        "configuration changes come first"
+    );
+    assert_eq!(changes[1].yanked().expect("yanked").version, "0.2.0");
+    assert_eq!(changes[2].added().expect("added").version, "0.3.0");
+
+    std::fs::remove_file(served.path().join("3/g/gix"))?;
+    let changes = index.fetch_changes(["GIX"])?;
+    assert_eq!(changes.len(), 1);
+    assert!(
+        matches!(
+            &changes[0],
+            Change::CrateDeleted { name, versions } if name == "gix" && versions.len() == 3
+        ),
+        "names are matched case-insensitively and reported like the git index does"
+    );
+    assert_eq!(
+        index.fetch_changes(["gix"])?.len(),
+        0,
+        "deleted crates aren't reported twice"
+    );
+    Ok(())
+}
+
+#[test]
+fn in_place_rewrites_are_modifications() -> crate::Result {
+    let served = TempDir::new()?;
+    let state = TempDir::new()?;
+    write(served.path(), "config.json", CONFIG)?;
+    write(served.path(), "3/g/gix", &lines(&[V1, V2]))?;
+    let index = SparseIndex::with_url(serve(served.path().to_owned())?, state.path())?;
+    assert_eq!(index.fetch_changes(["gix"])?.len(), 3);
+
+    write(
+        served.path(),
+        "3/g/gix",
+        &lines(&[V1_WITH_RUST_VERSION, V2]),
+    )?;
+    let changes = index.fetch_changes(["gix"])?;
+    assert_eq!(changes.len(), 1);
+    let (old, new) = changes[0].modified().expect("modified");
+    assert_eq!(old.rust_version, None);
+    assert_eq!(new.rust_version.as_deref(), Some("1.70"));
+    assert_eq!(changes[0].versions(), std::slice::from_ref(new));
+
+    let v1_reformatted = V1.replace(r#""deps":[],"#, "").replace(
+        r#""yanked":false"#,
+        r#""yanked":false,"deps":[],"rust_version":"1.70""#,
+    );
+    write(served.path(), "3/g/gix", &lines(&[&v1_reformatted, V2]))?;
+    assert_eq!(
+        index.fetch_changes(["gix"])?.len(),
+        0,
+        "changes to the representation only aren't modifications"
+    );
+    Ok(())
+}
+
+#[test]
+fn missing_config_is_an_error() -> crate::Result {
+    let served = TempDir::new()?;
+    let state = TempDir::new()?;
+    let index = SparseIndex::with_url(serve(served.path().to_owned())?, state.path())?;
+    assert!(matches!(
+        index.peek_changes(["gix"]),
+        Err(crates_index_diff::sparse::Error::MissingConfig { .. })
+    ));
+    Ok(())
+}
+
+#[test]
+fn invalid_crate_names_are_errors() -> crate::Result {
+    let served = TempDir::new()?;
+    let state = TempDir::new()?;
+    write(served.path(), "config.json", CONFIG)?;
+    let index = SparseIndex::with_url(serve(served.path().to_owned())?, state.path())?;
+    for name in ["", "éa", "ab/../../x"] {
+        assert!(matches!(
+            index.peek_changes([name]),
+            Err(crates_index_diff::sparse::Error::InvalidCrateName { .. })
+        ));
+    }
+    Ok(())
+}
+
+fn lines(lines: &[&str]) -> String {
+    lines.iter().map(|l| format!("{l}\n")).collect()
+}
+
+fn write(root: &Path, relative_path: &str, content: &str) -> std::io::Result<()> {
+    let path = root.join(relative_path);
+    std::fs::create_dir_all(path.parent().expect("not the root"))?;
+    std::fs::write(path, content)
+}
+
+/// Serve the files in `root` over HTTP, supporting `ETag`s, and return the URL to reach them.
+fn serve(root: PathBuf) -> std::io::Result<String> {
+    let listener = TcpListener::bind("127.0.0.1:0")?;
+    let url = format!("http://{}", listener.local_addr()?);
+    std::thread::spawn(move || {
+        for stream in listener.incoming() {
+            let Ok(mut stream) = stream else { continue };
+            let mut reader = BufReader::new(&mut stream);
+            let mut request_line = String::new();
+            if reader.read_line(&mut request_line).is_err() {
+                continue;
+            }
+            let mut if_none_match = None;
+            loop {
+                let mut header = String::new();
+                if reader.read_line(&mut header).is_err() || header.trim().is_empty() {
+                    break;
+                }
+                if let Some((name, value)) = header.split_once(':') {
+                    if name.eq_ignore_ascii_case("if-none-match") {
+                        if_none_match = Some(value.trim().to_owned());
+                    }
+                }
+            }
+            let path = request_line.split(' ').nth(1).unwrap_or("/");
+            let response = match std::fs::read(root.join(path.trim_start_matches('/'))) {
+                Ok(data) => {
+                    let mut hasher = std::collections::hash_map::DefaultHasher::new();
+                    data.hash(&mut hasher);
+                    let etag = format!("\"{:x}\"", hasher.finish());
+                    if if_none_match.as_deref() == Some(etag.as_str()) {
+                        "HTTP/1.1 304 Not Modified\r\nConnection: close\r\n\r\n"
+                            .as_bytes()
+                            .to_vec()
+                    } else {
+                        let mut response = format!(
+                            "HTTP/1.1 200 OK\r\nETag: {etag}\r\nContent-Length: {}\r\nConnection: close\r\n\r\n",
+                            data.len()
+                        )
+                        .into_bytes();
+                        response.extend(data);
+                        response
+                    }
+                }
+                Err(_) => {
+                    "HTTP/1.1 404 Not Found\r\nContent-Length: 0\r\nConnection: close\r\n\r\n"
+                        .as_bytes()
+                        .to_vec()
+                }
+            };
+            stream.write_all(&response).ok();
+        }
+    });
+    Ok(url)
+}
@@ -25,6 +25,11 @@ fn parse_crate_version() {
             version: "1.0.0".into(),
             dependencies: Vec::new(),
             features: HashMap::new(),
+            features2: HashMap::new(),
+            schema_version: None,
+            links: None,
+            rust_version: None,
+            pubtime: None,
             checksum: Default::default()
         }
     );
@@ -51,7 +56,8 @@ fn parse_dependency() {
             default_features: true,
             target: None,
             kind: None,
-            package: None
+            package: None,
+            registry: None
         }
     );
 }
@@ -97,10 +103,102 @@ fn parse_crate_version_with_dependencies() {
                 default_features: true,
                 target: Some("main".into()),
                 kind: Some(DependencyKind::Dev),
-                package: Some("dep_package".into())
+                package: Some("dep_package".into()),
+                registry: None
             }],
             features: HashMap::new(),
+            features2: HashMap::new(),
+            schema_version: None,
+            links: None,
+            rust_version: None,
+            pubtime: None,
             checksum: Default::default()
         }
     );
 }
+
+#[test]
+fn parse_crate_version_with_modern_schema() {
+    let c: CrateVersion = serde_json::from_value(json!({
+        "name": "test",
+        "vers": "1.0.0",
+        "cksum": "0000000000000000000000000000000000000000000000000000000000000000",
+        "features" : {"default": ["std"], "std": []},
+        "features2" : {"serde": ["dep:serde"], "std": ["serde?/std"]},
+        "deps" : [
+            {
+                "name": "serde",
+                "req": "^1",
+                "features": [],
+                "optional": true,
+                "default_features": true,
+                "target": null,
+                "kind": "normal",
+                "registry": "https://github.com/rust-lang/crates.io-index"
+            }
+        ],
+        "yanked": false,
+        "links": "z",
+        "v": 2,
+        "rust_version": "1.70",
+        "pubtime": "2024-01-01T00:00:00Z"
+    }))
+    .unwrap();
+    assert_eq!(c.schema_version, Some(2));
+    assert_eq!(c.links.as_deref(), Some("z"));
+    assert_eq!(c.rust_version.as_deref(), Some("1.70"));
+    assert_eq!(c.pubtime.as_deref(), Some("2024-01-01T00:00:00Z"));
+    assert_eq!(
+        c.dependencies[0].registry.as_deref(),
+        Some("https://github.com/rust-lang/crates.io-index")
+    );
+
+    let features = c.all_features();
+    assert_eq!(features.len(), 3, "default, std and serde");
+    assert_eq!(features["serde"], ["dep:serde"]);
+    assert_eq!(
+        features["std"],
+        ["serde?/std"],
+        "features of both maps are combined"
+    );
+
+    let roundtripped: CrateVersion =
+        serde_json::from_str(&serde_json::to_string(&c).unwrap()).unwrap();
+    assert_eq!(roundtripped, c, "all new fields survive serialization");
+}
+
+#[test]
+fn invalid_semver_is_an_error() {
+    let c: CrateVersion = serde_json::from_value(json!({
+        "name": "test",
+        "vers": "1.0",
+        "cksum": "0000000000000000000000000000000000000000000000000000000000000000",
+        "features" : {},
+        "deps" : [
+            {
+                "name": "valid",
+                "req": "^1.2.3",
+                "features": [],
+                "optional": false,
+                "default_features": true
+            },
+            {
+                "name": "invalid",
+                "req": "^1.2.3.4",
+                "features": [],
+                "optional": false,
+                "default_features": true
+            }
+        ],
+        "yanked": false
+    }))
+    .unwrap();
+    assert!(c.try_version().is_err());
+    assert!(c.dependencies[0].try_required_version().is_ok());
+    assert!(c.dependencies[1].try_required_version().is_err());
+    let invalid: Vec<_> = c
+        .invalid_dependencies()
+        .map(|(dep, _err)| dep.name.as_str())
+        .collect();
+    assert_eq!(invalid, ["invalid"]);
+}